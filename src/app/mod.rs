@@ -1,2 +1,3 @@
 pub mod state;
 pub mod commands;
+pub mod saved_connections;
@@ -1,20 +1,40 @@
 use mongodb::Client;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Mutex, Arc, OnceLock};
 use serde::{Serialize, Deserialize};
 use tokio::sync::mpsc;
 
+use crate::mongo::client::{ClientSettings, PoolSettings};
 use crate::mongo::cursor_engine::CursorSession;
+use crate::app::saved_connections::SavedConnection;
 
 // Static storage for change stream events (accessible from background tasks)
 pub static CHANGE_STREAM_EVENTS: OnceLock<Arc<Mutex<HashMap<String, Vec<serde_json::Value>>>>> = OnceLock::new();
 
+// Cumulative per-stream counters, maintained by the listener tasks so they survive buffer eviction
+pub static CHANGE_STREAM_STATS: OnceLock<Arc<Mutex<HashMap<String, ChangeStreamStats>>>> = OnceLock::new();
+
+// Collection size samples recorded by the size-tracking background tasks, oldest first
+pub type SizeHistory = Mutex<HashMap<String, VecDeque<SizeSample>>>;
+pub static SIZE_HISTORY: OnceLock<Arc<SizeHistory>> = OnceLock::new();
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionInfo {
     pub id: String,
     pub name: String,
     pub uri: String,
     pub connected_at: chrono::DateTime<chrono::Utc>,
+    pub group: Option<String>,
+    /// Client-side guard: mutating commands are refused before reaching the server
+    pub read_only: bool,
+    pub default_db: Option<String>,
+    pub default_collection: Option<String>,
+    /// Effective pool size and timeouts the client was built with
+    pub pool: PoolSettings,
+    /// Overrides the client was built with, reused by `reconnect_all`. Never
+    /// serialized since it can hold credentials.
+    #[serde(skip)]
+    pub settings: ClientSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +47,95 @@ pub struct ChangeStreamInfo {
     pub operation_types: Vec<String>,
     pub started_at: chrono::DateTime<chrono::Utc>,
     pub is_active: bool,
+    pub persist_path: Option<String>,
+    /// Dotted path (e.g. `documentKey._id`) whose repeated events are collapsed to the latest
+    pub coalesce_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangeStreamStats {
+    pub total_events: u64,
+    pub operation_counts: HashMap<String, u64>,
+    pub last_event_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip)]
+    pub recent_events: VecDeque<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ChangeStreamStats {
+    pub fn record(&mut self, operation_type: &str) {
+        let now = chrono::Utc::now();
+        self.total_events += 1;
+        *self.operation_counts.entry(operation_type.to_string()).or_insert(0) += 1;
+        self.last_event_at = Some(now);
+        self.recent_events.push_back(now);
+        self.prune_recent(now);
+    }
+
+    pub fn events_in_last_minute(&mut self) -> usize {
+        self.prune_recent(chrono::Utc::now());
+        self.recent_events.len()
+    }
+
+    fn prune_recent(&mut self, now: chrono::DateTime<chrono::Utc>) {
+        let cutoff = now - chrono::Duration::minutes(1);
+        while self.recent_events.front().is_some_and(|t| *t < cutoff) {
+            self.recent_events.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeTrackingInfo {
+    pub id: String,
+    pub connection_id: String,
+    pub database: String,
+    pub collection: String,
+    pub interval_secs: u64,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeSample {
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+    pub size: i64,
+    pub count: i64,
+    pub storage_size: i64,
+}
+
+/// A tailable cursor following new inserts into a capped collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TailInfo {
+    pub id: String,
+    pub connection_id: String,
+    pub database: String,
+    pub collection: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A find result set kept current by a change stream on the same namespace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveQueryInfo {
+    pub id: String,
+    pub connection_id: String,
+    pub database: String,
+    pub collection: String,
+    pub filter: serde_json::Value,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Last `serverStatus.opcounters` reading for a connection, diffed on the next sample
+#[derive(Debug, Clone)]
+pub struct OpCounterSample {
+    pub counters: HashMap<String, i64>,
+    pub sampled_at: std::time::Instant,
+}
+
+/// A result set pinned by the user for later comparison
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultSnapshot {
+    pub name: String,
+    pub documents: Vec<serde_json::Value>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
 pub struct AppState {
@@ -37,6 +146,52 @@ pub struct AppState {
     pub change_streams: Mutex<HashMap<String, ChangeStreamInfo>>,
     pub change_stream_senders: Mutex<HashMap<String, mpsc::UnboundedSender<serde_json::Value>>>,
     pub change_stream_events: Mutex<HashMap<String, Vec<serde_json::Value>>>,
+    pub change_stream_tasks: Mutex<HashMap<String, tokio::task::AbortHandle>>,
+    pub size_trackers: Mutex<HashMap<String, SizeTrackingInfo>>,
+    pub size_tracking_tasks: Mutex<HashMap<String, tokio::task::AbortHandle>>,
+    pub tails: Mutex<HashMap<String, TailInfo>>,
+    pub tail_tasks: Mutex<HashMap<String, tokio::task::AbortHandle>>,
+    pub live_queries: Mutex<HashMap<String, LiveQueryInfo>>,
+    pub live_query_tasks: Mutex<HashMap<String, tokio::task::AbortHandle>>,
+    pub history_dedup: Mutex<bool>,
+    pub index_suggestions: Mutex<bool>,
+    pub slow_query_threshold_ms: Mutex<Option<u64>>,
+    /// Upper bound for opening a find/aggregate cursor, enforced server- and client-side
+    pub max_query_duration_ms: Mutex<Option<u64>>,
+    pub snapshots: Mutex<HashMap<String, ResultSnapshot>>,
+    pub op_counter_samples: Mutex<HashMap<String, OpCounterSample>>,
+    /// Bounds concurrent query/CRUD commands per connection; excess callers queue
+    pub connection_semaphores: Mutex<HashMap<String, Arc<tokio::sync::Semaphore>>>,
+    pub saved_connections: Mutex<Vec<SavedConnection>>,
+}
+
+impl AppState {
+    pub fn new(saved_connections: Vec<SavedConnection>) -> Self {
+        AppState {
+            clients: Mutex::new(HashMap::new()),
+            connections: Mutex::new(HashMap::new()),
+            cursors: Mutex::new(HashMap::new()),
+            query_history: Mutex::new(Vec::new()),
+            change_streams: Mutex::new(HashMap::new()),
+            change_stream_senders: Mutex::new(HashMap::new()),
+            change_stream_events: Mutex::new(HashMap::new()),
+            change_stream_tasks: Mutex::new(HashMap::new()),
+            size_trackers: Mutex::new(HashMap::new()),
+            size_tracking_tasks: Mutex::new(HashMap::new()),
+            tails: Mutex::new(HashMap::new()),
+            tail_tasks: Mutex::new(HashMap::new()),
+            live_queries: Mutex::new(HashMap::new()),
+            live_query_tasks: Mutex::new(HashMap::new()),
+            history_dedup: Mutex::new(false),
+            index_suggestions: Mutex::new(false),
+            slow_query_threshold_ms: Mutex::new(None),
+            max_query_duration_ms: Mutex::new(None),
+            snapshots: Mutex::new(HashMap::new()),
+            op_counter_samples: Mutex::new(HashMap::new()),
+            connection_semaphores: Mutex::new(HashMap::new()),
+            saved_connections: Mutex::new(saved_connections),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,4 +204,37 @@ pub struct QueryHistoryEntry {
     pub query: serde_json::Value,
     pub executed_at: chrono::DateTime<chrono::Utc>,
     pub execution_time_ms: Option<u64>,
+    pub comment: Option<String>,
+    pub tags: Vec<String>,
+    pub run_count: u32,
+    pub warnings: Vec<String>,
+    pub is_slow: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_stream_stats_tally_each_operation_type() {
+        let mut stats = ChangeStreamStats::default();
+        for operation_type in ["insert", "update", "insert", "delete", "insert"] {
+            stats.record(operation_type);
+        }
+        assert_eq!(stats.total_events, 5);
+        assert_eq!(stats.operation_counts.get("insert"), Some(&3));
+        assert_eq!(stats.operation_counts.get("update"), Some(&1));
+        assert_eq!(stats.operation_counts.get("delete"), Some(&1));
+        assert!(stats.last_event_at.is_some());
+    }
+
+    #[test]
+    fn change_stream_stats_rate_covers_the_last_minute() {
+        let mut stats = ChangeStreamStats::default();
+        let now = chrono::Utc::now();
+        stats.recent_events.extend([90, 61, 30].map(|secs| now - chrono::Duration::seconds(secs)));
+        stats.record("insert");
+        assert_eq!(stats.events_in_last_minute(), 2);
+        assert_eq!(stats.total_events, 1);
+    }
 }
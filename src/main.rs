@@ -5,67 +5,161 @@ mod utils;
 
 use app::state::AppState;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 fn main() {
     // Initialize static event storage
     app::state::CHANGE_STREAM_EVENTS.set(Arc::new(Mutex::new(HashMap::new())))
         .expect("Failed to initialize change stream events storage");
+    app::state::CHANGE_STREAM_STATS.set(Arc::new(Mutex::new(HashMap::new())))
+        .expect("Failed to initialize change stream stats storage");
+    app::state::SIZE_HISTORY.set(Arc::new(Mutex::new(HashMap::new())))
+        .expect("Failed to initialize size history storage");
+
+    // Saved connection definitions are offered for one-click reconnect
+    let saved_connections = app::saved_connections::load_all().unwrap_or_default();
     
     tauri::Builder::default()
-        .manage(AppState {
-            clients: std::sync::Mutex::new(HashMap::new()),
-            connections: std::sync::Mutex::new(HashMap::new()),
-            cursors: std::sync::Mutex::new(HashMap::new()),
-            query_history: std::sync::Mutex::new(Vec::new()),
-            change_streams: std::sync::Mutex::new(HashMap::new()),
-            change_stream_senders: std::sync::Mutex::new(HashMap::new()),
-            change_stream_events: std::sync::Mutex::new(HashMap::new()),
-        })
+        .manage(AppState::new(saved_connections))
         .invoke_handler(tauri::generate_handler![
             // Connection Management
+            app::commands::validate_uri,
             app::commands::connect_db,
+            app::commands::connect_db_retry,
+            app::commands::connect_x509,
+            app::commands::connect_aws,
             app::commands::disconnect_db,
             app::commands::list_connections,
             app::commands::get_connection,
+            app::commands::set_connection_group,
+            app::commands::set_connection_context,
+            app::commands::set_connection_concurrency,
+            app::commands::save_connection,
+            app::commands::list_saved_connections,
+            app::commands::delete_saved_connection,
+            app::commands::connect_saved,
+            app::commands::reconnect_all,
             // Database Operations
             app::commands::list_databases,
             app::commands::list_collections,
             // Query Operations
             app::commands::start_find,
+            app::commands::find_all,
             app::commands::start_aggregate,
+            app::commands::aggregate_db,
+            app::commands::aggregate_explained,
             app::commands::explain_query,
+            app::commands::summarize_explain,
+            app::commands::export_explain,
+            app::commands::count_documents,
+            app::commands::estimate_selectivity,
+            app::commands::find_page,
+            app::commands::find_after,
             app::commands::get_collection_stats,
+            app::commands::collection_overview,
+            app::commands::start_size_tracking,
+            app::commands::get_size_history,
+            app::commands::stop_size_tracking,
+            app::commands::get_plan_cache,
+            app::commands::clear_plan_cache,
+            app::commands::profiler_summary,
             app::commands::list_indexes,
             app::commands::fetch_next,
+            app::commands::set_adaptive_batching,
+            app::commands::get_cursor_info,
+            app::commands::touch_cursor,
             app::commands::cancel_query,
+            app::commands::set_max_query_duration_ms,
+            app::commands::kill_connection_cursors,
+            // Aggregation Helpers
+            app::commands::run_facets,
+            app::commands::field_histogram,
+            app::commands::validate_pipeline,
+            app::commands::build_lookup_stage,
+            app::commands::build_graph_lookup_stage,
             // CRUD Operations
             app::commands::insert_document,
             app::commands::insert_many_documents,
+            app::commands::estimate_document_size,
+            app::commands::generate_object_id,
+            app::commands::generate_uuid,
+            app::commands::object_id_timestamp,
             app::commands::update_document,
+            app::commands::patch_document,
             app::commands::update_many_documents,
+            app::commands::update_by_ids,
+            app::commands::convert_field_type,
             app::commands::delete_document,
             app::commands::delete_many_documents,
+            app::commands::delete_by_ids,
             app::commands::replace_document,
+            app::commands::dequeue,
+            app::commands::copy_collection,
+            app::commands::import_file,
+            app::commands::bulk_write,
             // Export Operations
             app::commands::export_results,
             // Query History
             app::commands::get_query_history,
             app::commands::clear_query_history,
             app::commands::delete_query_history_entry,
+            app::commands::tag_query_history,
+            app::commands::export_query_history,
+            app::commands::set_history_dedup,
+            app::commands::set_index_suggestions,
+            app::commands::set_slow_query_threshold_ms,
+            // Result Snapshots
+            app::commands::snapshot_results,
+            app::commands::list_snapshots,
+            app::commands::delete_snapshot,
+            app::commands::diff_snapshots,
             // Change Streams (Real-time Monitoring)
             app::commands::start_change_stream,
             app::commands::stop_change_stream,
+            app::commands::stop_all_change_streams,
             app::commands::list_change_streams,
             app::commands::get_change_stream_events,
             app::commands::clear_change_stream_events,
             app::commands::poll_change_stream_events,
+            app::commands::export_change_stream_events,
+            app::commands::get_change_stream_stats,
+            app::commands::change_stream_metrics,
+            app::commands::decode_resume_token,
+            app::commands::set_pre_post_images,
+            app::commands::tail_collection,
+            app::commands::stop_tail,
+            app::commands::start_live_query,
+            app::commands::stop_live_query,
             // Index Management
             app::commands::create_index,
+            app::commands::create_indexes,
             app::commands::drop_index,
             app::commands::drop_all_indexes,
             app::commands::rebuild_indexes,
+            app::commands::copy_indexes,
             app::commands::get_index_usage_stats,
+            app::commands::find_unused_indexes,
+            app::commands::index_coverage_report,
             app::commands::get_index_recommendations,
+            app::commands::compare_index_impact,
+            app::commands::suggest_covered_projection,
+            // Schema Analysis
+            app::commands::detect_type_mismatches,
+            app::commands::generate_json_schema,
+            app::commands::diff_schemas,
+            // Server Administration
+            app::commands::get_log,
+            app::commands::host_info,
+            app::commands::connection_op_metrics,
+            app::commands::get_fcv,
+            app::commands::create_user,
+            app::commands::drop_user,
+            app::commands::list_users,
+            app::commands::grant_roles,
+            app::commands::revoke_roles,
+            app::commands::validate_collection,
+            app::commands::repair_database,
+            app::commands::shard_key_info,
         ])
         .run(tauri::generate_context!())
         .expect("error running NovaDB Studio");
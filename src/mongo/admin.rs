@@ -0,0 +1,616 @@
+use mongodb::{Client, Database, bson::{Bson, Document, doc}};
+use serde::Deserialize;
+use serde_json::Value;
+use futures::StreamExt;
+
+pub async fn get_log(
+    client: &Client,
+    log_name: &str,
+) -> mongodb::error::Result<Document> {
+    client
+        .database("admin")
+        .run_command(doc! { "getLog": log_name }, None)
+        .await
+}
+
+pub async fn host_info(
+    client: &Client,
+) -> mongodb::error::Result<Document> {
+    client
+        .database("admin")
+        .run_command(doc! { "hostInfo": 1 }, None)
+        .await
+}
+
+/// Cumulative operation counters (`insert`, `query`, `update`, ...) from `serverStatus`
+pub async fn opcounters(
+    client: &Client,
+) -> mongodb::error::Result<Document> {
+    let status = client
+        .database("admin")
+        .run_command(doc! { "serverStatus": 1, "repl": 0, "metrics": 0, "locks": 0 }, None)
+        .await?;
+
+    Ok(status.get_document("opcounters").cloned().unwrap_or_default())
+}
+
+pub async fn get_fcv(
+    client: &Client,
+) -> mongodb::error::Result<Document> {
+    let response = client
+        .database("admin")
+        .run_command(doc! { "getParameter": 1, "featureCompatibilityVersion": 1 }, None)
+        .await?;
+
+    fcv_from_response(response)
+}
+
+/// The `featureCompatibilityVersion` document of a `getParameter` reply; a reply
+/// without one is an error rather than an empty version
+fn fcv_from_response(response: Document) -> mongodb::error::Result<Document> {
+    #[derive(Deserialize)]
+    struct GetParameterReply {
+        #[serde(rename = "featureCompatibilityVersion")]
+        fcv: Document,
+    }
+
+    Ok(mongodb::bson::from_document::<GetParameterReply>(response)?.fcv)
+}
+
+/// Full server version string (e.g. `"7.0.4"`) from `buildInfo`
+pub async fn build_version(
+    client: &Client,
+) -> mongodb::error::Result<String> {
+    let build_info = client
+        .database("admin")
+        .run_command(doc! { "buildInfo": 1 }, None)
+        .await?;
+
+    Ok(build_info.get_str("version").unwrap_or_default().to_string())
+}
+
+/// Server `(major, minor)` version from `buildInfo`
+pub async fn server_version(
+    client: &Client,
+) -> mongodb::error::Result<(u32, u32)> {
+    let version = build_version(client).await?;
+    let mut parts = version.split('.').map(|p| p.parse::<u32>().unwrap_or(0));
+    Ok((parts.next().unwrap_or(0), parts.next().unwrap_or(0)))
+}
+
+/// Whether the authenticated user holds every one of `actions` (e.g. `insert`,
+/// `remove`) on `db.coll`, read from `connectionStatus`. Deployments without
+/// authentication always allow it.
+pub async fn can_write_to(
+    client: &Client,
+    db: &str,
+    coll: &str,
+    actions: &[&str],
+) -> mongodb::error::Result<bool> {
+    let status = client
+        .database("admin")
+        .run_command(doc! { "connectionStatus": 1, "showPrivileges": true }, None)
+        .await?;
+
+    Ok(status_allows(&status, db, coll, actions))
+}
+
+fn status_allows(status: &Document, db: &str, coll: &str, actions: &[&str]) -> bool {
+    let Ok(auth_info) = status.get_document("authInfo") else {
+        return true;
+    };
+    if auth_info.get_array("authenticatedUsers").map(|u| u.is_empty()).unwrap_or(true) {
+        return true;
+    }
+
+    // An empty db or collection in a resource matches every database or collection
+    let privileges = auth_info.get_array("authenticatedUserPrivileges").cloned().unwrap_or_default();
+    let granted: Vec<&str> = privileges
+        .iter()
+        .filter_map(|p| p.as_document())
+        .filter(|privilege| {
+            let Ok(resource) = privilege.get_document("resource") else {
+                return false;
+            };
+            resource.get_bool("anyResource").unwrap_or(false)
+                || (matches!(resource.get_str("db"), Ok(d) if d.is_empty() || d == db)
+                    && matches!(resource.get_str("collection"), Ok(c) if c.is_empty() || c == coll))
+        })
+        .filter_map(|privilege| privilege.get_array("actions").ok())
+        .flatten()
+        .filter_map(|action| action.as_str())
+        .collect();
+
+    granted.contains(&"anyAction") || actions.iter().all(|action| granted.contains(action))
+}
+
+/// Convert a JSON roles array into BSON, accepting built-in role names
+/// (`"readWrite"`) or `{role, db}` documents for cross-database grants
+pub fn parse_roles(roles: Vec<Value>) -> Result<Vec<Bson>, String> {
+    if roles.is_empty() {
+        return Err("Roles must be a non-empty array".to_string());
+    }
+
+    roles
+        .into_iter()
+        .map(|role| match role {
+            Value::String(name) if !name.is_empty() => Ok(Bson::String(name)),
+            Value::Object(ref map) => {
+                let role_name = map.get("role").and_then(|r| r.as_str()).unwrap_or("");
+                let db_name = map.get("db").and_then(|d| d.as_str()).unwrap_or("");
+                if role_name.is_empty() || db_name.is_empty() {
+                    return Err(format!("Role document must have non-empty 'role' and 'db' fields: {}", role));
+                }
+                Ok(Bson::Document(doc! { "role": role_name, "db": db_name }))
+            }
+            other => Err(format!("Invalid role: {}. Use a role name or a {{role, db}} document", other)),
+        })
+        .collect()
+}
+
+pub async fn create_user(
+    database: Database,
+    username: &str,
+    password: &str,
+    roles: Vec<Bson>,
+) -> mongodb::error::Result<()> {
+    database.run_command(
+        doc! {
+            "createUser": username,
+            "pwd": password,
+            "roles": roles,
+        },
+        None,
+    ).await?;
+
+    Ok(())
+}
+
+pub async fn drop_user(
+    database: Database,
+    username: &str,
+) -> mongodb::error::Result<()> {
+    database.run_command(doc! { "dropUser": username }, None).await?;
+    Ok(())
+}
+
+pub async fn list_users(
+    database: Database,
+) -> mongodb::error::Result<Vec<Document>> {
+    let response = database.run_command(doc! { "usersInfo": 1 }, None).await?;
+    Ok(users_from_reply(&response))
+}
+
+fn users_from_reply(response: &Document) -> Vec<Document> {
+    response
+        .get_array("users")
+        .map(|users| {
+            users
+                .iter()
+                .filter_map(|u| u.as_document().cloned())
+                .map(|mut user| {
+                    // Never hand credential material to the UI
+                    user.remove("credentials");
+                    user
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub async fn grant_roles(
+    database: Database,
+    username: &str,
+    roles: Vec<Bson>,
+) -> mongodb::error::Result<()> {
+    database.run_command(
+        doc! {
+            "grantRolesToUser": username,
+            "roles": roles,
+        },
+        None,
+    ).await?;
+
+    Ok(())
+}
+
+pub async fn revoke_roles(
+    database: Database,
+    username: &str,
+    roles: Vec<Bson>,
+) -> mongodb::error::Result<()> {
+    database.run_command(
+        doc! {
+            "revokeRolesFromUser": username,
+            "roles": roles,
+        },
+        None,
+    ).await?;
+
+    Ok(())
+}
+
+pub async fn validate_collection(
+    database: Database,
+    collection_name: &str,
+    full: bool,
+) -> mongodb::error::Result<Document> {
+    let response = database.run_command(
+        doc! {
+            "validate": collection_name,
+            "full": full,
+        },
+        None,
+    ).await?;
+
+    Ok(doc! {
+        "ns": response.get("ns").cloned().unwrap_or(Bson::Null),
+        "valid": response.get_bool("valid").unwrap_or(false),
+        "full": full,
+        "warnings": response.get("warnings").cloned().unwrap_or_else(|| Bson::Array(Vec::new())),
+        "errors": response.get("errors").cloned().unwrap_or_else(|| Bson::Array(Vec::new())),
+        "nrecords": response.get("nrecords").cloned().unwrap_or(Bson::Null),
+        "nIndexes": response.get("nIndexes").cloned().unwrap_or(Bson::Null),
+        "keysPerIndex": response.get("keysPerIndex").cloned().unwrap_or(Bson::Null),
+    })
+}
+
+async fn storage_size_summary(database: &Database) -> mongodb::error::Result<Document> {
+    let stats = database.run_command(doc! { "dbStats": 1 }, None).await?;
+
+    Ok(doc! {
+        "dataSize": stats.get("dataSize").cloned().unwrap_or(Bson::Null),
+        "storageSize": stats.get("storageSize").cloned().unwrap_or(Bson::Null),
+        "indexSize": stats.get("indexSize").cloned().unwrap_or(Bson::Null),
+    })
+}
+
+/// Reclaim disk space for a database. `repairDatabase` was removed in MongoDB 4.2,
+/// so servers that no longer know the command fall back to `compact` per collection.
+pub async fn repair_database(
+    client: &Client,
+    db: &str,
+) -> mongodb::error::Result<Document> {
+    let database = client.database(db);
+    let before = storage_size_summary(&database).await?;
+
+    let mut compacted = Vec::new();
+    let method = match database.run_command(doc! { "repairDatabase": 1 }, None).await {
+        Ok(_) => "repairDatabase",
+        Err(e) if matches!(*e.kind, mongodb::error::ErrorKind::Command(ref c) if c.code == 59) => {
+            // CommandNotFound
+            let collections = database
+                .list_collection_names(doc! { "type": "collection" })
+                .await?;
+            for collection_name in collections {
+                database.run_command(doc! { "compact": &collection_name }, None).await?;
+                compacted.push(collection_name);
+            }
+            "compact"
+        }
+        Err(e) => return Err(e),
+    };
+
+    let after = storage_size_summary(&database).await?;
+
+    Ok(doc! {
+        "database": db,
+        "method": method,
+        "compacted_collections": compacted,
+        "before": before,
+        "after": after,
+    })
+}
+
+/// Shard key from `config.collections` plus chunk and document counts per shard.
+/// Chunks are matched by `uuid` (5.0+) or `ns` (older servers).
+pub async fn shard_key_info(
+    client: &Client,
+    db: &str,
+    collection: &str,
+) -> mongodb::error::Result<Document> {
+    let namespace = format!("{}.{}", db, collection);
+    let config = client.database("config");
+
+    let entry = config
+        .collection::<Document>("collections")
+        .find_one(doc! { "_id": &namespace }, None)
+        .await?;
+    let entry = match entry {
+        Some(entry) if !entry.get_bool("dropped").unwrap_or(false) => entry,
+        _ => {
+            return Ok(doc! {
+                "namespace": &namespace,
+                "sharded": false,
+                "message": "Collection is not sharded",
+            });
+        }
+    };
+
+    let mut chunk_match = vec![Bson::Document(doc! { "ns": &namespace })];
+    if let Some(uuid) = entry.get("uuid") {
+        chunk_match.push(Bson::Document(doc! { "uuid": uuid.clone() }));
+    }
+    let mut chunks = config
+        .collection::<Document>("chunks")
+        .aggregate(vec![
+            doc! { "$match": { "$or": chunk_match } },
+            doc! { "$group": { "_id": "$shard", "chunks": { "$sum": 1 } } },
+            doc! { "$sort": { "_id": 1 } },
+        ], None)
+        .await?;
+
+    let mut shards: Vec<Document> = Vec::new();
+    while let Some(result) = chunks.next().await {
+        let group = result?;
+        shards.push(doc! {
+            "shard": group.get("_id").cloned().unwrap_or(Bson::Null),
+            "chunks": group.get("chunks").cloned().unwrap_or(Bson::Int32(0)),
+            "documents": Bson::Null,
+        });
+    }
+
+    // `$collStats` through mongos reports one entry per shard
+    let mut counts = client
+        .database(db)
+        .collection::<Document>(collection)
+        .aggregate(vec![doc! { "$collStats": { "count": {} } }], None)
+        .await?;
+    while let Some(result) = counts.next().await {
+        let stats = result?;
+        let Ok(shard) = stats.get_str("shard") else { continue };
+        let count = stats.get("count").cloned().unwrap_or(Bson::Null);
+        match shards.iter_mut().find(|s| s.get_str("shard") == Ok(shard)) {
+            Some(existing) => {
+                existing.insert("documents", count);
+            }
+            None => shards.push(doc! { "shard": shard, "chunks": 0, "documents": count }),
+        }
+    }
+
+    let total_chunks: i64 = shards.iter().map(|s| match s.get("chunks") {
+        Some(Bson::Int32(n)) => *n as i64,
+        Some(Bson::Int64(n)) => *n,
+        _ => 0,
+    }).sum();
+
+    Ok(doc! {
+        "namespace": &namespace,
+        "sharded": true,
+        "key": entry.get_document("key").cloned().unwrap_or_default(),
+        "unique": entry.get_bool("unique").unwrap_or(false),
+        "total_chunks": total_chunks,
+        "shards": shards,
+    })
+}
+
+/// `killOp` every in-progress operation whose command carries `comment`,
+/// returning how many were killed
+pub async fn kill_ops_by_comment(
+    client: &Client,
+    comment: &str,
+) -> mongodb::error::Result<usize> {
+    let admin = client.database("admin");
+    let mut operations = admin
+        .aggregate(vec![
+            doc! { "$currentOp": { "allUsers": true } },
+            doc! { "$match": { "command.comment": comment } },
+        ], None)
+        .await?;
+
+    let mut killed = 0;
+    while let Some(result) = operations.next().await {
+        let operation = result?;
+        if let Some(opid) = operation.get("opid") {
+            admin.run_command(doc! { "killOp": 1, "op": opid.clone() }, None).await?;
+            killed += 1;
+        }
+    }
+
+    Ok(killed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::test_support::{scratch_database, test_client};
+
+    #[test]
+    fn fcv_is_read_from_the_reply() {
+        let fcv = fcv_from_response(doc! { "featureCompatibilityVersion": { "version": "7.0" }, "ok": 1.0 }).unwrap();
+        assert_eq!(fcv, doc! { "version": "7.0" });
+    }
+
+    #[test]
+    fn missing_fcv_is_an_error() {
+        let error = fcv_from_response(doc! { "ok": 1.0 }).unwrap_err();
+        assert!(error.to_string().contains("featureCompatibilityVersion"), "{}", error);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn get_log_returns_log_lines() {
+        let log = get_log(&test_client().await, "global").await.unwrap();
+        assert!(log.get_array("log").is_ok(), "{:?}", log);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn get_fcv_returns_a_version() {
+        let fcv = get_fcv(&test_client().await).await.unwrap();
+        let version = fcv.get_str("version").unwrap();
+        let parts: Vec<&str> = version.split('.').collect();
+        assert_eq!(parts.len(), 2, "{}", version);
+        assert!(parts.iter().all(|p| p.parse::<u32>().is_ok()), "{}", version);
+    }
+
+    fn status_with(privileges: Vec<Document>) -> Document {
+        doc! { "authInfo": {
+            "authenticatedUsers": [{ "user": "etl", "db": "admin" }],
+            "authenticatedUserPrivileges": privileges,
+        } }
+    }
+
+    #[test]
+    fn unauthenticated_deployments_allow_writes() {
+        assert!(status_allows(&doc! { "authInfo": { "authenticatedUsers": [] } }, "reports", "daily", &["insert", "remove"]));
+    }
+
+    #[test]
+    fn every_action_must_be_granted_on_the_target() {
+        let status = status_with(vec![
+            doc! { "resource": { "db": "reports", "collection": "" }, "actions": ["find", "insert"] },
+            doc! { "resource": { "db": "reports", "collection": "daily" }, "actions": ["remove"] },
+            doc! { "resource": { "db": "shop", "collection": "" }, "actions": ["update"] },
+        ]);
+        // Cross-database $out needs insert and remove on the target database
+        assert!(status_allows(&status, "reports", "daily", &["insert", "remove"]));
+        assert!(!status_allows(&status, "reports", "weekly", &["insert", "remove"]));
+        assert!(!status_allows(&status, "reports", "daily", &["insert", "update"]));
+        assert!(!status_allows(&status, "shop", "orders", &["insert", "update"]));
+    }
+
+    #[test]
+    fn any_action_on_any_resource_allows_everything() {
+        let status = status_with(vec![doc! { "resource": { "anyResource": true }, "actions": ["anyAction"] }]);
+        assert!(status_allows(&status, "reports", "daily", &["insert", "update"]));
+    }
+
+    #[test]
+    fn roles_must_not_be_empty() {
+        assert_eq!(parse_roles(Vec::new()).unwrap_err(), "Roles must be a non-empty array");
+        assert_eq!(parse_roles(vec![serde_json::json!("read")]).unwrap(), vec![Bson::String("read".into())]);
+    }
+
+    #[test]
+    fn listed_users_never_carry_credentials() {
+        let reply = doc! { "users": [{
+            "user": "reporter",
+            "db": "shop",
+            "roles": [{ "role": "read", "db": "shop" }],
+            "credentials": { "SCRAM-SHA-256": { "storedKey": "abc", "serverKey": "def" } },
+        }], "ok": 1.0 };
+        let users = users_from_reply(&reply);
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].get_str("user"), Ok("reporter"));
+        assert!(!users[0].contains_key("credentials"));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn read_only_user_lifecycle() {
+        let db = scratch_database().await;
+        let roles = parse_roles(vec![serde_json::json!("read")]).unwrap();
+        create_user(db.clone(), "reporter", "s3cret-pass", roles).await.unwrap();
+
+        let users = list_users(db.clone()).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].get_str("user"), Ok("reporter"));
+        assert_eq!(users[0].get_array("roles").unwrap(), &vec![Bson::Document(doc! { "role": "read", "db": db.name() })]);
+        assert!(!users[0].contains_key("credentials"));
+
+        drop_user(db.clone(), "reporter").await.unwrap();
+        assert!(list_users(db.clone()).await.unwrap().is_empty());
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn roles_accept_names_and_cross_database_documents() {
+        let roles = parse_roles(vec![serde_json::json!("readWrite"), serde_json::json!({ "role": "read", "db": "reports" })]).unwrap();
+        assert_eq!(roles, vec![Bson::String("readWrite".into()), Bson::Document(doc! { "role": "read", "db": "reports" })]);
+
+        assert!(parse_roles(vec![serde_json::json!("")]).is_err());
+        assert!(parse_roles(vec![serde_json::json!({ "role": "read" })]).is_err());
+        assert!(parse_roles(vec![serde_json::json!(42)]).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn granted_roles_can_be_revoked() {
+        let db = scratch_database().await;
+        let role_names = |users: Vec<Document>| -> Vec<String> {
+            let mut names: Vec<String> = users[0]
+                .get_array("roles")
+                .unwrap()
+                .iter()
+                .filter_map(|r| r.as_document()?.get_str("role").ok().map(String::from))
+                .collect();
+            names.sort();
+            names
+        };
+        create_user(db.clone(), "etl", "s3cret-pass", parse_roles(vec![serde_json::json!("read")]).unwrap()).await.unwrap();
+
+        grant_roles(db.clone(), "etl", parse_roles(vec![serde_json::json!("readWrite")]).unwrap()).await.unwrap();
+        assert_eq!(role_names(list_users(db.clone()).await.unwrap()), vec!["read", "readWrite"]);
+
+        revoke_roles(db.clone(), "etl", parse_roles(vec![serde_json::json!("readWrite")]).unwrap()).await.unwrap();
+        assert_eq!(role_names(list_users(db.clone()).await.unwrap()), vec!["read"]);
+
+        drop_user(db.clone(), "etl").await.unwrap();
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn healthy_collection_validates() {
+        let db = scratch_database().await;
+        db.collection::<Document>("items").insert_many((0..10).map(|i| doc! { "_id": i }), None).await.unwrap();
+
+        for full in [false, true] {
+            let report = validate_collection(db.clone(), "items", full).await.unwrap();
+            assert_eq!(report.get_bool("valid"), Ok(true), "{:?}", report);
+            assert!(report.get_array("errors").unwrap().is_empty());
+        }
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a standalone MongoDB server at MONGODB_TEST_URI"]
+    async fn repair_database_runs_on_a_standalone() {
+        let db = scratch_database().await;
+        db.collection::<Document>("items").insert_many((0..10).map(|i| doc! { "_id": i }), None).await.unwrap();
+
+        let report = repair_database(&test_client().await, db.name()).await.unwrap();
+        match report.get_str("method").unwrap() {
+            "compact" => assert_eq!(report.get_array("compacted_collections").unwrap(), &vec![Bson::String("items".into())]),
+            method => assert_eq!(method, "repairDatabase"),
+        }
+        assert!(report.get_document("before").unwrap().contains_key("storageSize"));
+        assert!(report.get_document("after").unwrap().contains_key("storageSize"));
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn unsharded_collections_say_so() {
+        let db = scratch_database().await;
+        db.collection::<Document>("items").insert_one(doc! { "_id": 1 }, None).await.unwrap();
+
+        let info = shard_key_info(&test_client().await, db.name(), "items").await.unwrap();
+        assert_eq!(info.get_bool("sharded"), Ok(false));
+        assert_eq!(info.get_str("message"), Ok("Collection is not sharded"));
+
+        db.drop(None).await.unwrap();
+    }
+
+    /// Needs `MONGODB_TEST_SHARDED_URI` pointing at a mongos
+    #[tokio::test]
+    #[ignore = "requires a sharded MongoDB cluster"]
+    async fn sharded_collections_report_their_key() {
+        let uri = std::env::var("MONGODB_TEST_SHARDED_URI").expect("MONGODB_TEST_SHARDED_URI is not set");
+        let client = Client::with_uri_str(uri).await.unwrap();
+        let db = client.database(&format!("novadb_test_{}", uuid::Uuid::new_v4().simple()));
+        let namespace = format!("{}.orders", db.name());
+        let admin = client.database("admin");
+        admin.run_command(doc! { "enableSharding": db.name() }, None).await.unwrap();
+        admin.run_command(doc! { "shardCollection": &namespace, "key": { "customer": "hashed" } }, None).await.unwrap();
+        db.collection::<Document>("orders").insert_many((0..100).map(|i| doc! { "customer": i }), None).await.unwrap();
+
+        let info = shard_key_info(&client, db.name(), "orders").await.unwrap();
+        assert_eq!(info.get_bool("sharded"), Ok(true));
+        assert_eq!(info.get_document("key").unwrap(), &doc! { "customer": "hashed" });
+        assert!(info.get_i64("total_chunks").unwrap() >= 1);
+
+        db.drop(None).await.unwrap();
+    }
+}
@@ -1,8 +1,577 @@
-use mongodb::{Collection, bson::Document};
+use mongodb::{Collection, Database, bson::{Bson, Document, doc}, options::AggregateOptions};
+use serde::Serialize;
+use futures::StreamExt;
 
 pub async fn aggregate(
     collection: Collection<Document>,
     pipeline: Vec<Document>,
+    comment: Option<String>,
+    max_time_ms: Option<u64>,
 ) -> mongodb::error::Result<mongodb::Cursor<Document>> {
-    collection.aggregate(pipeline, None).await
+    let mut options = AggregateOptions::default();
+    options.comment = comment;
+    options.max_time = max_time_ms.map(std::time::Duration::from_millis);
+    collection.aggregate(pipeline, Some(options)).await
+}
+
+/// Run a database-level pipeline (`$currentOp`, `$listLocalSessions`, `$documents`, ...)
+pub async fn aggregate_database(
+    database: Database,
+    pipeline: Vec<Document>,
+    comment: Option<String>,
+    max_time_ms: Option<u64>,
+) -> mongodb::error::Result<mongodb::Cursor<Document>> {
+    let mut options = AggregateOptions::default();
+    options.comment = comment;
+    options.max_time = max_time_ms.map(std::time::Duration::from_millis);
+    database.aggregate(pipeline, Some(options)).await
+}
+
+fn require_non_empty(name: &str, value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("'{}' cannot be empty", name));
+    }
+    Ok(())
+}
+
+/// Reject pipelines that write their results (`$out` / `$merge`) where only reading makes sense
+pub fn reject_output_stages(pipeline: &[Document]) -> Result<(), String> {
+    for (index, stage) in pipeline.iter().enumerate() {
+        if let Some(name) = stage.keys().find(|k| *k == "$out" || *k == "$merge") {
+            return Err(format!("Stage {} ({}) is not allowed here", index, name));
+        }
+    }
+    Ok(())
+}
+
+/// Namespace a pipeline's terminal `$out` / `$merge` stage writes to
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputTarget {
+    pub stage: String,
+    pub database: String,
+    pub collection: String,
+}
+
+impl OutputTarget {
+    /// Privilege actions the stage needs on the target: `$out` replaces the
+    /// collection, `$merge` inserts and updates documents in it
+    pub fn required_actions(&self) -> &'static [&'static str] {
+        match self.stage.as_str() {
+            "$out" => &["insert", "remove"],
+            _ => &["insert", "update"],
+        }
+    }
+}
+
+/// Resolve where a pipeline writes its results. Both stages accept a bare collection
+/// name (written to `default_db`) or a `{db, coll}` document; `$merge` nests either
+/// form under `into`.
+pub fn output_target(pipeline: &[Document], default_db: &str) -> Result<Option<OutputTarget>, String> {
+    let Some(last) = pipeline.last() else {
+        return Ok(None);
+    };
+    let (stage, spec) = match (last.get("$out"), last.get("$merge")) {
+        (Some(spec), _) => ("$out", spec),
+        (_, Some(Bson::Document(merge))) => ("$merge", merge.get("into").ok_or("$merge requires an 'into' target")?),
+        (_, Some(spec)) => ("$merge", spec),
+        _ => return Ok(None),
+    };
+
+    let (database, collection) = match spec {
+        Bson::String(coll) => (default_db.to_string(), coll.clone()),
+        Bson::Document(target) => {
+            let coll = target.get_str("coll").map_err(|_| format!("{} target requires a 'coll' name", stage))?;
+            let db = target.get_str("db").unwrap_or(default_db);
+            (db.to_string(), coll.to_string())
+        }
+        _ => return Err(format!("{} target must be a collection name or {{db, coll}}", stage)),
+    };
+    require_non_empty("coll", &collection)?;
+    require_non_empty("db", &database)?;
+
+    Ok(Some(OutputTarget { stage: stage.to_string(), database, collection }))
+}
+
+const KNOWN_STAGES: &[&str] = &[
+    "$addFields", "$bucket", "$bucketAuto", "$changeStream", "$changeStreamSplitLargeEvent",
+    "$collStats", "$count", "$currentOp", "$densify", "$documents", "$facet", "$fill",
+    "$geoNear", "$graphLookup", "$group", "$indexStats", "$limit", "$listLocalSessions",
+    "$listSampledQueries", "$listSearchIndexes", "$listSessions", "$lookup", "$match", "$merge",
+    "$out", "$planCacheStats", "$project", "$redact", "$replaceRoot", "$replaceWith", "$sample",
+    "$search", "$searchMeta", "$set", "$setWindowFields", "$skip", "$sort", "$sortByCount",
+    "$unionWith", "$unset", "$unwind", "$vectorSearch",
+];
+
+/// Stages the server only accepts as the first stage of a pipeline
+const FIRST_ONLY_STAGES: &[&str] = &[
+    "$changeStream", "$collStats", "$currentOp", "$documents", "$geoNear", "$indexStats",
+    "$listLocalSessions", "$listSessions", "$planCacheStats", "$search", "$searchMeta", "$vectorSearch",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineIssue {
+    pub stage_index: usize,
+    pub stage: Option<String>,
+    pub message: String,
+}
+
+/// Lint a pipeline without running it: every stage must be a single-key object naming a
+/// known stage, `$out`/`$merge` must come last and first-only stages must come first
+pub fn validate_pipeline(pipeline: &[Bson]) -> Vec<PipelineIssue> {
+    let mut issues = Vec::new();
+    let last = pipeline.len().saturating_sub(1);
+
+    for (index, stage) in pipeline.iter().enumerate() {
+        let mut issue = |stage: Option<&str>, message: String| issues.push(PipelineIssue {
+            stage_index: index,
+            stage: stage.map(|s| s.to_string()),
+            message,
+        });
+
+        let stage_doc = match stage {
+            Bson::Document(doc) => doc,
+            _ => {
+                issue(None, "Stage must be an object".to_string());
+                continue;
+            }
+        };
+        if stage_doc.len() != 1 {
+            issue(None, format!("Stage must have exactly one key, found {}", stage_doc.len()));
+            continue;
+        }
+
+        let name = stage_doc.keys().next().map(|k| k.as_str()).unwrap_or_default();
+        if !KNOWN_STAGES.contains(&name) {
+            issue(Some(name), format!("Unknown stage '{}'", name));
+        } else if (name == "$out" || name == "$merge") && index != last {
+            issue(Some(name), format!("{} must be the last stage", name));
+        } else if FIRST_ONLY_STAGES.contains(&name) && index != 0 {
+            issue(Some(name), format!("{} must be the first stage", name));
+        }
+    }
+
+    issues
+}
+
+/// Build an equality-match `$lookup` stage
+pub fn build_lookup(
+    from: &str,
+    local_field: &str,
+    foreign_field: &str,
+    as_field: &str,
+) -> Result<Document, String> {
+    require_non_empty("from", from)?;
+    require_non_empty("localField", local_field)?;
+    require_non_empty("foreignField", foreign_field)?;
+    require_non_empty("as", as_field)?;
+
+    Ok(doc! {
+        "$lookup": {
+            "from": from,
+            "localField": local_field,
+            "foreignField": foreign_field,
+            "as": as_field,
+        }
+    })
+}
+
+/// Build a `$lookup` stage using the sub-pipeline form with optional `let` variables
+pub fn build_lookup_pipeline(
+    from: &str,
+    let_vars: Option<Document>,
+    pipeline: Vec<Document>,
+    as_field: &str,
+) -> Result<Document, String> {
+    require_non_empty("from", from)?;
+    require_non_empty("as", as_field)?;
+
+    let mut lookup = doc! { "from": from };
+    if let Some(vars) = let_vars {
+        lookup.insert("let", vars);
+    }
+    lookup.insert("pipeline", pipeline);
+    lookup.insert("as", as_field);
+
+    Ok(doc! { "$lookup": lookup })
+}
+
+/// Run several sub-pipelines in one pass with `$facet`, optionally preceded by a `$match`
+pub async fn run_facets(
+    collection: Collection<Document>,
+    filter: Option<Document>,
+    facets: Document,
+) -> mongodb::error::Result<Document> {
+    let mut pipeline = Vec::new();
+    if let Some(filter_doc) = filter {
+        pipeline.push(doc! { "$match": filter_doc });
+    }
+    pipeline.push(doc! { "$facet": facets });
+
+    let mut cursor = collection.aggregate(pipeline, None).await?;
+    match cursor.next().await {
+        Some(result) => result,
+        None => Ok(Document::new()),
+    }
+}
+
+const HISTOGRAM_TYPES: [&str; 2] = ["number", "date"];
+
+/// `$facet` sub-pipelines for a histogram of `field`: `buckets` holds the `$bucketAuto`
+/// distribution over numeric and date values, `bucketed_types` the types that went into
+/// it and `other_types` the types of any values that could not be bucketed
+pub fn histogram_facets(field: &str, buckets: u32) -> Result<Document, String> {
+    require_non_empty("field", field)?;
+    if field.starts_with('$') {
+        return Err("'field' must be a field path without a leading '$'".to_string());
+    }
+    if buckets == 0 {
+        return Err("'buckets' must be at least 1".to_string());
+    }
+
+    let group_by = format!("${}", field);
+    Ok(doc! {
+        "buckets": [
+            { "$match": { field: { "$type": HISTOGRAM_TYPES.to_vec() } } },
+            { "$bucketAuto": { "groupBy": group_by.clone(), "buckets": buckets as i32 } },
+        ],
+        "bucketed_types": [
+            { "$match": { field: { "$type": HISTOGRAM_TYPES.to_vec() } } },
+            { "$group": { "_id": { "$type": group_by.clone() } } },
+        ],
+        "other_types": [
+            { "$match": { field: { "$exists": true, "$not": { "$type": HISTOGRAM_TYPES.to_vec() } } } },
+            { "$group": { "_id": { "$type": group_by } } },
+        ],
+    })
+}
+
+fn facet_types(result: &Document, facet: &str) -> Vec<String> {
+    result.get_array(facet).cloned().unwrap_or_default()
+        .iter()
+        .filter_map(|t| t.as_document().and_then(|d| d.get_str("_id").ok()).map(str::to_string))
+        .collect()
+}
+
+/// The `[{min, max, count}]` buckets of a `histogram_facets` result. Numbers and dates
+/// cannot share one axis, so a field holding both is an error, as is one holding neither.
+pub fn histogram_buckets(result: &Document, field: &str) -> Result<Vec<Document>, String> {
+    let bucketed_types = facet_types(result, "bucketed_types");
+    if bucketed_types.iter().any(|t| t == "date") && bucketed_types.iter().any(|t| t != "date") {
+        return Err(format!(
+            "Field '{}' mixes numeric and date values; filter on one type (e.g. {{\"{}\": {{\"$type\": \"date\"}}}}) to chart it",
+            field, field
+        ));
+    }
+
+    let bucket_docs = result.get_array("buckets").cloned().unwrap_or_default();
+    if bucket_docs.is_empty() {
+        let other_types = facet_types(result, "other_types");
+        if !other_types.is_empty() {
+            return Err(format!(
+                "Field '{}' has no numeric or date values (found {})",
+                field,
+                other_types.join(", ")
+            ));
+        }
+    }
+
+    Ok(bucket_docs
+        .into_iter()
+        .filter_map(|bucket| bucket.as_document().cloned())
+        .map(|bucket| {
+            let bounds = bucket.get_document("_id").cloned().unwrap_or_default();
+            doc! {
+                "min": bounds.get("min").cloned().unwrap_or(Bson::Null),
+                "max": bounds.get("max").cloned().unwrap_or(Bson::Null),
+                "count": bucket.get("count").cloned().unwrap_or(Bson::Int32(0)),
+            }
+        })
+        .collect())
+}
+
+/// Build a recursive `$graphLookup` stage. `start_with` is an expression such
+/// as `"$reportsTo"`; `max_depth` of `None` means unlimited recursion.
+pub fn build_graph_lookup(
+    from: &str,
+    start_with: &str,
+    connect_from: &str,
+    connect_to: &str,
+    as_field: &str,
+    max_depth: Option<i64>,
+) -> Result<Document, String> {
+    require_non_empty("from", from)?;
+    require_non_empty("startWith", start_with)?;
+    require_non_empty("connectFromField", connect_from)?;
+    require_non_empty("connectToField", connect_to)?;
+    require_non_empty("as", as_field)?;
+    if let Some(depth) = max_depth {
+        if depth < 0 {
+            return Err("'maxDepth' must be a non-negative integer".to_string());
+        }
+    }
+
+    let mut graph_lookup = doc! {
+        "from": from,
+        "startWith": start_with,
+        "connectFromField": connect_from,
+        "connectToField": connect_to,
+        "as": as_field,
+    };
+    if let Some(depth) = max_depth {
+        graph_lookup.insert("maxDepth", depth);
+    }
+
+    Ok(doc! { "$graphLookup": graph_lookup })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::test_support::scratch_database;
+
+    fn stages(pipeline: Vec<Document>) -> Vec<Bson> {
+        pipeline.into_iter().map(Bson::Document).collect()
+    }
+
+    #[test]
+    fn validate_pipeline_accepts_a_valid_pipeline() {
+        let pipeline = stages(vec![
+            doc! { "$match": { "status": "active" } },
+            doc! { "$group": { "_id": "$city", "n": { "$sum": 1 } } },
+            doc! { "$out": "by_city" },
+        ]);
+        assert!(validate_pipeline(&pipeline).is_empty());
+    }
+
+    #[test]
+    fn validate_pipeline_reports_unknown_stages() {
+        let issues = validate_pipeline(&stages(vec![doc! { "$match": {} }, doc! { "$mtach": {} }]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].stage_index, 1);
+        assert_eq!(issues[0].stage.as_deref(), Some("$mtach"));
+        assert_eq!(issues[0].message, "Unknown stage '$mtach'");
+    }
+
+    #[test]
+    fn validate_pipeline_reports_misplaced_stages() {
+        let issues = validate_pipeline(&stages(vec![
+            doc! { "$out": "copy" },
+            doc! { "$match": {} },
+            doc! { "$documents": [] },
+        ]));
+        let messages: Vec<(usize, &str)> = issues.iter().map(|i| (i.stage_index, i.message.as_str())).collect();
+        assert_eq!(messages, vec![
+            (0, "$out must be the last stage"),
+            (2, "$documents must be the first stage"),
+        ]);
+    }
+
+    #[test]
+    fn validate_pipeline_reports_malformed_stages() {
+        let pipeline = vec![Bson::String("$match".to_string()), Bson::Document(doc! { "$match": {}, "$limit": 1 })];
+        let messages: Vec<String> = validate_pipeline(&pipeline).into_iter().map(|i| i.message).collect();
+        assert_eq!(messages, vec!["Stage must be an object", "Stage must have exactly one key, found 2"]);
+    }
+
+    #[test]
+    fn build_lookup_builds_an_equality_lookup() {
+        assert_eq!(
+            build_lookup("customers", "customer_id", "_id", "customer").unwrap(),
+            doc! { "$lookup": { "from": "customers", "localField": "customer_id", "foreignField": "_id", "as": "customer" } }
+        );
+    }
+
+    #[test]
+    fn build_lookup_requires_every_field() {
+        let error = build_lookup("customers", " ", "_id", "customer").unwrap_err();
+        assert_eq!(error, "'localField' cannot be empty");
+        assert!(build_lookup("", "a", "b", "c").is_err());
+        assert!(build_lookup("a", "b", "", "c").is_err());
+        assert!(build_lookup("a", "b", "c", "").is_err());
+    }
+
+    #[test]
+    fn build_graph_lookup_with_max_depth() {
+        assert_eq!(
+            build_graph_lookup("employees", "$reportsTo", "reportsTo", "name", "chain", Some(2)).unwrap(),
+            doc! { "$graphLookup": {
+                "from": "employees",
+                "startWith": "$reportsTo",
+                "connectFromField": "reportsTo",
+                "connectToField": "name",
+                "as": "chain",
+                "maxDepth": 2_i64,
+            } }
+        );
+    }
+
+    #[test]
+    fn build_graph_lookup_without_max_depth_is_unbounded() {
+        let stage = build_graph_lookup("employees", "$reportsTo", "reportsTo", "name", "chain", None).unwrap();
+        let spec = stage.get_document("$graphLookup").unwrap();
+        assert!(!spec.contains_key("maxDepth"));
+        assert_eq!(spec.len(), 5);
+    }
+
+    #[test]
+    fn build_graph_lookup_rejects_bad_input() {
+        assert!(build_graph_lookup("employees", "$reportsTo", "reportsTo", "name", "chain", Some(-1)).is_err());
+        assert!(build_graph_lookup("employees", "", "reportsTo", "name", "chain", None).is_err());
+        assert!(build_graph_lookup("employees", "$reportsTo", "reportsTo", " ", "chain", None).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn aggregate_database_runs_a_documents_pipeline() {
+        let db = scratch_database().await;
+        let pipeline = vec![
+            doc! { "$documents": [{ "x": 1 }, { "x": 2 }, { "x": 3 }] },
+            doc! { "$match": { "x": { "$gte": 2 } } },
+        ];
+        let cursor = aggregate_database(db, pipeline, None, None).await.unwrap();
+        let documents: Vec<Document> = cursor.map(|d| d.unwrap()).collect().await;
+        assert_eq!(documents, vec![doc! { "x": 2 }, doc! { "x": 3 }]);
+    }
+
+    #[test]
+    fn output_target_reads_every_out_and_merge_form() {
+        let target = |stage: Document| output_target(&[doc! { "$match": {} }, stage], "shop").unwrap().unwrap();
+
+        let out = target(doc! { "$out": "copy" });
+        assert_eq!((out.stage.as_str(), out.database.as_str(), out.collection.as_str()), ("$out", "shop", "copy"));
+        let out = target(doc! { "$out": { "db": "reports", "coll": "daily" } });
+        assert_eq!((out.database.as_str(), out.collection.as_str()), ("reports", "daily"));
+        assert_eq!(out.required_actions(), ["insert", "remove"]);
+
+        let merge = target(doc! { "$merge": { "into": { "db": "reports", "coll": "daily" }, "on": "_id" } });
+        assert_eq!((merge.stage.as_str(), merge.database.as_str(), merge.collection.as_str()), ("$merge", "reports", "daily"));
+        assert_eq!(merge.required_actions(), ["insert", "update"]);
+        assert_eq!(target(doc! { "$merge": "copy" }).collection, "copy");
+
+        assert!(output_target(&[doc! { "$match": {} }], "shop").unwrap().is_none());
+        assert!(output_target(&[doc! { "$out": { "db": "reports" } }], "shop").is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn cross_database_out_writes_to_the_target_namespace() {
+        let source_db = scratch_database().await;
+        let target_db = scratch_database().await;
+        let source = source_db.collection::<Document>("orders");
+        source.insert_many(vec![doc! { "_id": 1, "total": 5 }, doc! { "_id": 2, "total": 50 }], None).await.unwrap();
+
+        let pipeline = vec![
+            doc! { "$match": { "total": { "$gt": 10 } } },
+            doc! { "$out": { "db": target_db.name(), "coll": "big_orders" } },
+        ];
+        let target = output_target(&pipeline, source_db.name()).unwrap().unwrap();
+        assert_eq!((target.database.as_str(), target.collection.as_str()), (target_db.name(), "big_orders"));
+
+        aggregate(source, pipeline, None, None).await.unwrap();
+        let written: Vec<Document> = target_db.collection::<Document>("big_orders").find(None, None).await.unwrap()
+            .map(|d| d.unwrap()).collect().await;
+        assert_eq!(written, vec![doc! { "_id": 2, "total": 50 }]);
+
+        source_db.drop(None).await.unwrap();
+        target_db.drop(None).await.unwrap();
+    }
+
+    fn types(names: &[&str]) -> Vec<Document> {
+        names.iter().map(|name| doc! { "_id": *name }).collect()
+    }
+
+    #[test]
+    fn histogram_buckets_flatten_bucket_bounds() {
+        let result = doc! {
+            "buckets": [
+                { "_id": { "min": 0, "max": 10 }, "count": 4 },
+                { "_id": { "min": 10, "max": 25.5 }, "count": 2 },
+            ],
+            "bucketed_types": types(&["int", "double"]),
+            "other_types": types(&["string"]),
+        };
+        assert_eq!(histogram_buckets(&result, "price").unwrap(), vec![
+            doc! { "min": 0, "max": 10, "count": 4 },
+            doc! { "min": 10, "max": 25.5, "count": 2 },
+        ]);
+    }
+
+    #[test]
+    fn histogram_buckets_reject_numbers_mixed_with_dates() {
+        let result = doc! { "buckets": [], "bucketed_types": types(&["date", "long"]), "other_types": [] };
+        assert!(histogram_buckets(&result, "at").unwrap_err().contains("mixes numeric and date"));
+    }
+
+    #[test]
+    fn histogram_buckets_reject_fields_without_numbers_or_dates() {
+        let result = doc! { "buckets": [], "bucketed_types": [], "other_types": types(&["string", "bool"]) };
+        assert_eq!(histogram_buckets(&result, "name").unwrap_err(), "Field 'name' has no numeric or date values (found string, bool)");
+        let empty = doc! { "buckets": [], "bucketed_types": [], "other_types": [] };
+        assert!(histogram_buckets(&empty, "name").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn histogram_bucket_counts_sum_to_the_matching_documents() {
+        let db = scratch_database().await;
+        let orders = db.collection::<Document>("orders");
+        let documents: Vec<Document> = (0..97).map(|i| doc! { "total": i * 3, "status": if i % 4 == 0 { "open" } else { "done" } }).collect();
+        orders.insert_many(documents, None).await.unwrap();
+
+        let filter = doc! { "status": "done" };
+        let matching = orders.count_documents(filter.clone(), None).await.unwrap();
+        let result = run_facets(orders, Some(filter), histogram_facets("total", 7).unwrap()).await.unwrap();
+        let buckets = histogram_buckets(&result, "total").unwrap();
+
+        assert!(!buckets.is_empty() && buckets.len() <= 7);
+        let counted: i64 = buckets.iter().map(|b| match b.get("count") {
+            Some(Bson::Int32(n)) => *n as i64,
+            Some(Bson::Int64(n)) => *n,
+            other => panic!("unexpected count {:?}", other),
+        }).sum();
+        assert_eq!(counted as u64, matching);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn run_facets_returns_every_facet() {
+        let db = scratch_database().await;
+        let orders = db.collection::<Document>("orders");
+        orders.insert_many(vec![
+            doc! { "status": "open", "price": 5 },
+            doc! { "status": "open", "price": 15 },
+            doc! { "status": "closed", "price": 25 },
+            doc! { "status": "void", "price": 35 },
+        ], None).await.unwrap();
+
+        let facets = doc! {
+            "by_status": [{ "$sortByCount": "$status" }, { "$sort": { "count": -1, "_id": 1 } }],
+            "prices": [{ "$bucket": { "groupBy": "$price", "boundaries": [0, 10, 20, 30] } }],
+        };
+        let result = run_facets(orders, Some(doc! { "status": { "$ne": "void" } }), facets).await.unwrap();
+
+        assert_eq!(result.get_array("by_status").unwrap(), &stages(vec![
+            doc! { "_id": "open", "count": 2 },
+            doc! { "_id": "closed", "count": 1 },
+        ]));
+        assert_eq!(result.get_array("prices").unwrap(), &stages(vec![
+            doc! { "_id": 0, "count": 1 },
+            doc! { "_id": 10, "count": 1 },
+            doc! { "_id": 20, "count": 1 },
+        ]));
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn transform_pipelines_may_not_write_output() {
+        assert!(reject_output_stages(&[doc! { "$project": { "name": 1 } }]).is_ok());
+        assert_eq!(
+            reject_output_stages(&[doc! { "$match": {} }, doc! { "$merge": { "into": "copy" } }]).unwrap_err(),
+            "Stage 1 ($merge) is not allowed here"
+        );
+        assert!(reject_output_stages(&[doc! { "$out": "copy" }]).is_err());
+    }
 }
@@ -1,21 +1,50 @@
-use mongodb::{Collection, Database, Client, bson::Document, change_stream::ChangeStream};
+use mongodb::{Collection, Database, bson::Document, change_stream::{ChangeStream, event::ChangeStreamEvent}};
+use mongodb::options::{ChangeStreamOptions, FullDocumentBeforeChangeType, FullDocumentType};
+
+/// Parse a `fullDocument` mode. `"default"` leaves the option unset so update events
+/// only carry the delta; no mode keeps the `updateLookup` behaviour.
+pub fn parse_full_document(mode: Option<&str>) -> Result<Option<FullDocumentType>, String> {
+    match mode {
+        None | Some("updateLookup") => Ok(Some(FullDocumentType::UpdateLookup)),
+        Some("default") => Ok(None),
+        Some("whenAvailable") => Ok(Some(FullDocumentType::WhenAvailable)),
+        Some("required") => Ok(Some(FullDocumentType::Required)),
+        Some(other) => Err(format!(
+            "Invalid full document mode '{}': expected default, updateLookup, whenAvailable or required",
+            other
+        )),
+    }
+}
+
+/// Pre-images (`fullDocumentBeforeChange`) are only returned when the watched
+/// collection has `changeStreamPreAndPostImages` enabled (see `set_pre_post_images`).
+fn build_options(
+    full_document: Option<FullDocumentType>,
+    full_document_before_change: Option<bool>,
+) -> ChangeStreamOptions {
+    let mut options = ChangeStreamOptions::default();
+    options.full_document = full_document;
+
+    if full_document_before_change.unwrap_or(false) {
+        options.full_document_before_change = Some(FullDocumentBeforeChangeType::WhenAvailable);
+    }
+
+    options
+}
 
 pub async fn watch_collection(
     collection: Collection<Document>,
     filter: Option<Document>,
     _operation_types: Option<Vec<String>>,
-) -> mongodb::error::Result<ChangeStream<Document>> {
-    use mongodb::options::ChangeStreamOptions;
-    
-    let mut options = ChangeStreamOptions::default();
-    
-    // Set full document option for better change event details
-    options.full_document = Some(mongodb::options::FullDocument::UpdateLookup);
+    full_document: Option<FullDocumentType>,
+    full_document_before_change: Option<bool>,
+) -> mongodb::error::Result<ChangeStream<ChangeStreamEvent<Document>>> {
+    let options = build_options(full_document, full_document_before_change);
     
     if let Some(filter_doc) = filter {
-        collection.watch_with_options(vec![filter_doc], options).await
+        collection.watch(vec![filter_doc], Some(options)).await
     } else {
-        collection.watch_with_options(vec![], options).await
+        collection.watch(vec![], Some(options)).await
     }
 }
 
@@ -23,16 +52,15 @@ pub async fn watch_database(
     database: Database,
     filter: Option<Document>,
     _operation_types: Option<Vec<String>>,
-) -> mongodb::error::Result<ChangeStream<Document>> {
-    use mongodb::options::ChangeStreamOptions;
-    
-    let mut options = ChangeStreamOptions::default();
-    options.full_document = Some(mongodb::options::FullDocument::UpdateLookup);
+    full_document: Option<FullDocumentType>,
+    full_document_before_change: Option<bool>,
+) -> mongodb::error::Result<ChangeStream<ChangeStreamEvent<Document>>> {
+    let options = build_options(full_document, full_document_before_change);
     
     if let Some(filter_doc) = filter {
-        database.watch_with_options(vec![filter_doc], options).await
+        database.watch(vec![filter_doc], Some(options)).await
     } else {
-        database.watch_with_options(vec![], options).await
+        database.watch(vec![], Some(options)).await
     }
 }
 
@@ -40,16 +68,152 @@ pub async fn watch_client(
     client: &mongodb::Client,
     filter: Option<Document>,
     _operation_types: Option<Vec<String>>,
-) -> mongodb::error::Result<ChangeStream<Document>> {
-    use mongodb::options::ChangeStreamOptions;
-    
-    let mut options = ChangeStreamOptions::default();
-    options.full_document = Some(mongodb::options::FullDocument::UpdateLookup);
+    full_document: Option<FullDocumentType>,
+    full_document_before_change: Option<bool>,
+) -> mongodb::error::Result<ChangeStream<ChangeStreamEvent<Document>>> {
+    let options = build_options(full_document, full_document_before_change);
     
     if let Some(filter_doc) = filter {
-        client.watch_with_options(vec![filter_doc], options).await
+        client.watch(vec![filter_doc], Some(options)).await
     } else {
-        client.watch_with_options(vec![], options).await
+        client.watch(vec![], Some(options)).await
+    }
+}
+
+/// Enable or disable `changeStreamPreAndPostImages` on a collection (MongoDB 6.0+)
+pub async fn set_pre_post_images(
+    database: Database,
+    collection_name: String,
+    enabled: bool,
+) -> mongodb::error::Result<()> {
+    database.run_command(
+        mongodb::bson::doc! {
+            "collMod": collection_name,
+            "changeStreamPreAndPostImages": { "enabled": enabled }
+        },
+        None,
+    ).await?;
+
+    Ok(())
+}
+
+/// KeyString type byte for a BSON timestamp, which every resume token starts with
+const KEYSTRING_TIMESTAMP: u8 = 130;
+
+/// Decode the cluster time from a resume token's `_data` hex string. The rest of
+/// the token is an opaque KeyString and is returned as-is.
+pub fn decode_resume_token(data: &str) -> Result<Document, String> {
+    let data = data.trim();
+    if !data.len().is_multiple_of(2) || !data.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("Resume token data must be a hex string".to_string());
+    }
+    let bytes: Vec<u8> = (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid resume token hex: {}", e))?;
+
+    if bytes.len() < 9 || bytes[0] != KEYSTRING_TIMESTAMP {
+        return Ok(mongodb::bson::doc! {
+            "data": data,
+            "decoded": false,
+            "message": "Token does not start with a cluster time; it is opaque to the client",
+        });
     }
+
+    let time = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+    let increment = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+
+    Ok(mongodb::bson::doc! {
+        "data": data,
+        "decoded": true,
+        "cluster_time": mongodb::bson::Timestamp { time, increment },
+        "wall_time": mongodb::bson::DateTime::from_millis(time as i64 * 1000),
+        "increment": increment as i64,
+    })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `_data` of an insert event's resume token, in the v1 KeyString format 4.2+ servers emit
+    const CAPTURED_TOKEN: &str = "8263F7A1E1000000012B022C0100296E5A1004A5093ABB38FE4B9EA67F01BB1A96D812463C5F6964003C6F72642D31000004";
+
+    #[test]
+    fn decode_resume_token_reads_the_cluster_time() {
+        let decoded = decode_resume_token(CAPTURED_TOKEN).unwrap();
+        assert_eq!(decoded.get_bool("decoded"), Ok(true));
+        assert_eq!(decoded.get_timestamp("cluster_time").unwrap(), mongodb::bson::Timestamp { time: 1677173217, increment: 1 });
+        assert_eq!(decoded.get_datetime("wall_time").unwrap().try_to_rfc3339_string().unwrap(), "2023-02-23T17:26:57Z");
+        assert_eq!(decoded.get_i64("increment"), Ok(1));
+    }
+
+    #[test]
+    fn decode_resume_token_leaves_other_tokens_opaque() {
+        let decoded = decode_resume_token("0100").unwrap();
+        assert_eq!(decoded.get_bool("decoded"), Ok(false));
+        assert!(decode_resume_token("82zz").is_err());
+        assert!(decode_resume_token("820").is_err());
+    }
+
+    #[test]
+    fn pre_images_are_only_requested_when_asked_for() {
+        assert!(build_options(None, None).full_document_before_change.is_none());
+        assert!(build_options(None, Some(false)).full_document_before_change.is_none());
+        assert!(matches!(
+            build_options(None, Some(true)).full_document_before_change,
+            Some(FullDocumentBeforeChangeType::WhenAvailable)
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB 6.0+ replica set at MONGODB_TEST_URI"]
+    async fn delete_events_carry_the_pre_image() {
+        use futures::StreamExt;
+
+        let db = crate::mongo::test_support::scratch_database().await;
+        db.create_collection("items", None).await.unwrap();
+        set_pre_post_images(db.clone(), "items".to_string(), true).await.unwrap();
+        let coll = db.collection::<Document>("items");
+        coll.insert_one(mongodb::bson::doc! { "_id": 1, "name": "widget" }, None).await.unwrap();
+
+        let mut stream = watch_collection(coll.clone(), None, None, None, Some(true)).await.unwrap();
+        coll.delete_one(mongodb::bson::doc! { "_id": 1 }, None).await.unwrap();
+
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.full_document_before_change, Some(mongodb::bson::doc! { "_id": 1, "name": "widget" }));
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn full_document_modes_parse() {
+        assert!(matches!(parse_full_document(None), Ok(Some(FullDocumentType::UpdateLookup))));
+        assert!(matches!(parse_full_document(Some("updateLookup")), Ok(Some(FullDocumentType::UpdateLookup))));
+        assert!(matches!(parse_full_document(Some("whenAvailable")), Ok(Some(FullDocumentType::WhenAvailable))));
+        assert!(matches!(parse_full_document(Some("required")), Ok(Some(FullDocumentType::Required))));
+        assert!(matches!(parse_full_document(Some("default")), Ok(None)));
+        assert!(parse_full_document(Some("lookup")).unwrap_err().starts_with("Invalid full document mode 'lookup'"));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB replica set at MONGODB_TEST_URI"]
+    async fn default_mode_updates_carry_only_the_delta() {
+        use futures::StreamExt;
+
+        let db = crate::mongo::test_support::scratch_database().await;
+        let coll = db.collection::<Document>("items");
+        coll.insert_one(mongodb::bson::doc! { "_id": 1, "name": "widget", "qty": 1 }, None).await.unwrap();
+
+        let mut stream = watch_collection(coll.clone(), None, None, parse_full_document(Some("default")).unwrap(), None).await.unwrap();
+        coll.update_one(mongodb::bson::doc! { "_id": 1 }, mongodb::bson::doc! { "$set": { "qty": 2 } }, None).await.unwrap();
+
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.full_document, None);
+        let update = event.update_description.unwrap();
+        assert_eq!(update.updated_fields, mongodb::bson::doc! { "qty": 2 });
+
+        db.drop(None).await.unwrap();
+    }
+}
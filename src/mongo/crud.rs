@@ -1,4 +1,4 @@
-use mongodb::{Collection, bson::Document, options::{InsertManyOptions, UpdateOptions, DeleteOptions}};
+use mongodb::{Collection, bson::Document, options::{InsertManyOptions, UpdateOptions, DeleteOptions, FindOneAndDeleteOptions, FindOneAndUpdateOptions, ReturnDocument}};
 use anyhow::Result;
 
 pub async fn insert_one(
@@ -20,6 +20,100 @@ pub async fn insert_many(
     collection.insert_many(documents, Some(options)).await
 }
 
+/// What `insert_many_with_policy` does when a document hits a duplicate key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    Error,
+    Skip,
+    Replace,
+}
+
+impl DuplicatePolicy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "error" => Ok(DuplicatePolicy::Error),
+            "skip" => Ok(DuplicatePolicy::Skip),
+            "replace" => Ok(DuplicatePolicy::Replace),
+            other => Err(format!("Invalid on_duplicate value '{}'. Use 'error', 'skip' or 'replace'", other)),
+        }
+    }
+}
+
+fn is_duplicate_key_error(error: &mongodb::error::Error) -> bool {
+    matches!(
+        *error.kind,
+        mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(ref write_error))
+            if write_error.code == 11000
+    )
+}
+
+/// Insert documents one at a time so duplicate-key conflicts can be skipped or
+/// turned into a replace by `_id`. Failures are reported per document; with
+/// `ordered` the batch stops at the first one, as `insertMany` would.
+/// Returns inserted/skipped/replaced counts and the errors.
+pub async fn insert_many_with_policy(
+    collection: Collection<Document>,
+    documents: Vec<Document>,
+    policy: DuplicatePolicy,
+    ordered: bool,
+) -> Document {
+    let (mut inserted, mut skipped, mut replaced) = (0i64, 0i64, 0i64);
+    let mut errors = Vec::new();
+
+    for (index, document) in documents.into_iter().enumerate() {
+        let outcome = match collection.insert_one(document.clone(), None).await {
+            Ok(_) => {
+                inserted += 1;
+                Ok(())
+            }
+            Err(e) if is_duplicate_key_error(&e) => match (policy, document.get("_id")) {
+                (DuplicatePolicy::Skip, _) => {
+                    skipped += 1;
+                    Ok(())
+                }
+                (DuplicatePolicy::Replace, Some(id)) => {
+                    match collection.replace_one(mongodb::bson::doc! { "_id": id.clone() }, document.clone(), None).await {
+                        // No document with this `_id`: the conflict is on another unique index
+                        Ok(result) if result.matched_count == 0 => Err(format!(
+                            "duplicate key on another unique index, no document with this _id to replace: {}",
+                            e
+                        )),
+                        Ok(_) => {
+                            replaced += 1;
+                            Ok(())
+                        }
+                        Err(replace_error) => Err(replace_error.to_string()),
+                    }
+                }
+                _ => Err(e.to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(message) = outcome {
+            errors.push(mongodb::bson::doc! { "index": index as i64, "error": message });
+            if ordered {
+                break;
+            }
+        }
+    }
+
+    mongodb::bson::doc! {
+        "inserted": inserted,
+        "skipped": skipped,
+        "replaced": replaced,
+        "errors": errors,
+    }
+}
+
+/// `{_id: {$in: ids}}`, refusing an empty selection so it can never match everything by mistake
+pub fn ids_filter(ids: Vec<mongodb::bson::Bson>) -> Result<Document, String> {
+    if ids.is_empty() {
+        return Err("At least one document id is required".to_string());
+    }
+    Ok(mongodb::bson::doc! { "_id": { "$in": ids } })
+}
+
 pub async fn update_one(
     collection: Collection<Document>,
     filter: Document,
@@ -46,6 +140,44 @@ pub async fn update_many(
     collection.update_many(filter, update, Some(options)).await
 }
 
+const CONVERTIBLE_TYPES: [&str; 8] = ["double", "int", "long", "decimal", "string", "bool", "date", "objectId"];
+
+/// Convert `field` to `target_type` with `$convert` in an update pipeline. Values that
+/// cannot be converted (e.g. non-numeric strings) keep their original value.
+pub async fn convert_field_type(
+    collection: Collection<Document>,
+    field: &str,
+    target_type: &str,
+    filter: Document,
+) -> Result<mongodb::results::UpdateResult, String> {
+    if field.trim().is_empty() || field.starts_with('$') {
+        return Err(format!("Invalid field name '{}'", field));
+    }
+    if !CONVERTIBLE_TYPES.contains(&target_type) {
+        return Err(format!(
+            "Unsupported target type '{}'. Use one of: {}",
+            target_type,
+            CONVERTIBLE_TYPES.join(", ")
+        ));
+    }
+
+    let input = format!("${}", field);
+    let pipeline = vec![mongodb::bson::doc! {
+        "$set": {
+            field: {
+                "$convert": {
+                    "input": &input,
+                    "to": target_type,
+                    "onError": &input,
+                    "onNull": &input,
+                }
+            }
+        }
+    }];
+
+    collection.update_many(filter, pipeline, None).await.map_err(|e| e.to_string())
+}
+
 pub async fn delete_one(
     collection: Collection<Document>,
     filter: Document,
@@ -73,3 +205,434 @@ pub async fn replace_one(
     collection.replace_one(filter, replacement, Some(options)).await
 }
 
+
+/// Atomically take the oldest document matching `filter` off a queue collection.
+/// With `mark_field` the document is claimed by setting that field to the current
+/// time (already-claimed documents are excluded); without it the document is removed.
+/// Returns `None` when nothing matches.
+pub async fn dequeue(
+    collection: Collection<Document>,
+    filter: Document,
+    sort: Option<Document>,
+    mark_field: Option<String>,
+) -> mongodb::error::Result<Option<Document>> {
+    let sort = sort.unwrap_or_else(|| mongodb::bson::doc! { "_id": 1 });
+
+    match mark_field {
+        Some(field) => {
+            let filter = unclaimed_filter(filter, &field);
+            let mut options = FindOneAndUpdateOptions::default();
+            options.sort = Some(sort);
+            options.return_document = Some(ReturnDocument::After);
+            collection.find_one_and_update(
+                filter,
+                mongodb::bson::doc! { "$set": { field: mongodb::bson::DateTime::now() } },
+                Some(options),
+            ).await
+        }
+        None => {
+            let mut options = FindOneAndDeleteOptions::default();
+            options.sort = Some(sort);
+            collection.find_one_and_delete(filter, Some(options)).await
+        }
+    }
+}
+
+/// `filter` restricted to documents without `mark_field`. The two are combined with
+/// `$and` so a condition the caller already has on `mark_field` is kept, not replaced.
+fn unclaimed_filter(filter: Document, mark_field: &str) -> Document {
+    let unclaimed = mongodb::bson::doc! { mark_field: { "$exists": false } };
+    if filter.is_empty() {
+        unclaimed
+    } else {
+        mongodb::bson::doc! { "$and": [filter, unclaimed] }
+    }
+}
+
+const COPY_BATCH_SIZE: usize = 1000;
+
+/// Copy every document of `source` into `destination`, optionally reshaping them
+/// with `transform_pipeline` on the way. Returns the number of documents inserted.
+pub async fn copy_collection(
+    source: Collection<Document>,
+    destination: Collection<Document>,
+    transform_pipeline: Option<Vec<Document>>,
+) -> mongodb::error::Result<u64> {
+    use futures::StreamExt;
+
+    let mut cursor = match transform_pipeline {
+        Some(pipeline) => source.aggregate(pipeline, None).await?,
+        None => source.find(None, None).await?,
+    };
+
+    let mut copied = 0u64;
+    let mut batch = Vec::with_capacity(COPY_BATCH_SIZE);
+    while let Some(document) = cursor.next().await {
+        batch.push(document?);
+        if batch.len() == COPY_BATCH_SIZE {
+            copied += destination.insert_many(std::mem::take(&mut batch), None).await?.inserted_ids.len() as u64;
+        }
+    }
+    if !batch.is_empty() {
+        copied += destination.insert_many(batch, None).await?.inserted_ids.len() as u64;
+    }
+
+    Ok(copied)
+}
+
+/// One write in a `bulk_write` batch, in the shell's `bulkWrite` shape
+/// (`{"updateOne": {"filter": ..., "update": ...}}`, `{"insertOne": {"document": ...}}`, ...)
+#[derive(Debug, Clone)]
+pub enum BulkOperation {
+    InsertOne(Document),
+    UpdateOne { filter: Document, update: Document, upsert: Option<bool> },
+    UpdateMany { filter: Document, update: Document, upsert: Option<bool> },
+    ReplaceOne { filter: Document, replacement: Document, upsert: Option<bool> },
+    DeleteOne(Document),
+    DeleteMany(Document),
+}
+
+impl BulkOperation {
+    pub fn parse(operation: &Document) -> Result<Self, String> {
+        let (name, body) = match operation.iter().next() {
+            Some((name, mongodb::bson::Bson::Document(body))) if operation.len() == 1 => (name.as_str(), body),
+            _ => return Err("Each operation must be a single-key object such as {\"updateOne\": {...}}".to_string()),
+        };
+        let field = |key: &str| body.get_document(key).cloned()
+            .map_err(|_| format!("{} requires a '{}' object", name, key));
+        let upsert = body.get_bool("upsert").ok();
+
+        match name {
+            "insertOne" => Ok(BulkOperation::InsertOne(field("document")?)),
+            "updateOne" => Ok(BulkOperation::UpdateOne { filter: field("filter")?, update: field("update")?, upsert }),
+            "updateMany" => Ok(BulkOperation::UpdateMany { filter: field("filter")?, update: field("update")?, upsert }),
+            "replaceOne" => Ok(BulkOperation::ReplaceOne { filter: field("filter")?, replacement: field("replacement")?, upsert }),
+            "deleteOne" => Ok(BulkOperation::DeleteOne(field("filter")?)),
+            "deleteMany" => Ok(BulkOperation::DeleteMany(field("filter")?)),
+            other => Err(format!("Unknown bulk operation '{}'", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BulkOperation::InsertOne(_) => "insertOne",
+            BulkOperation::UpdateOne { .. } => "updateOne",
+            BulkOperation::UpdateMany { .. } => "updateMany",
+            BulkOperation::ReplaceOne { .. } => "replaceOne",
+            BulkOperation::DeleteOne(_) => "deleteOne",
+            BulkOperation::DeleteMany(_) => "deleteMany",
+        }
+    }
+}
+
+/// Report what each operation would touch without writing anything: matched counts
+/// for updates/deletes (capped at 1 for the `One` variants) and validity for inserts
+pub async fn preview_bulk(
+    collection: Collection<Document>,
+    operations: &[BulkOperation],
+) -> mongodb::error::Result<Vec<Document>> {
+    let mut summary = Vec::with_capacity(operations.len());
+    for (index, operation) in operations.iter().enumerate() {
+        let mut entry = mongodb::bson::doc! { "index": index as i64, "operation": operation.name() };
+        match operation {
+            BulkOperation::InsertOne(document) => {
+                let check = crate::utils::size::check_document_size(document);
+                entry.insert("valid", check.is_ok());
+                if let Err(e) = check {
+                    entry.insert("error", e);
+                }
+            }
+            BulkOperation::UpdateOne { filter, upsert, .. } | BulkOperation::ReplaceOne { filter, upsert, .. } => {
+                let matched = collection.count_documents(filter.clone(), None).await?.min(1);
+                entry.insert("would_match", matched as i64);
+                entry.insert("would_upsert", matched == 0 && upsert.unwrap_or(false));
+            }
+            BulkOperation::UpdateMany { filter, upsert, .. } => {
+                let matched = collection.count_documents(filter.clone(), None).await?;
+                entry.insert("would_match", matched as i64);
+                entry.insert("would_upsert", matched == 0 && upsert.unwrap_or(false));
+            }
+            BulkOperation::DeleteOne(filter) => {
+                let matched = collection.count_documents(filter.clone(), None).await?.min(1);
+                entry.insert("would_match", matched as i64);
+            }
+            BulkOperation::DeleteMany(filter) => {
+                let matched = collection.count_documents(filter.clone(), None).await?;
+                entry.insert("would_match", matched as i64);
+            }
+        }
+        summary.push(entry);
+    }
+    Ok(summary)
+}
+
+/// Run the operations one after another, reporting a result per operation.
+/// With `ordered` the batch stops at the first failure.
+pub async fn execute_bulk(
+    collection: Collection<Document>,
+    operations: Vec<BulkOperation>,
+    ordered: bool,
+) -> Vec<Document> {
+    let mut results = Vec::with_capacity(operations.len());
+    for (index, operation) in operations.into_iter().enumerate() {
+        let name = operation.name();
+        let outcome = match operation {
+            BulkOperation::InsertOne(document) => collection.insert_one(document, None).await
+                .map(|r| mongodb::bson::doc! { "inserted_id": r.inserted_id }),
+            BulkOperation::UpdateOne { filter, update, upsert } => update_one(collection.clone(), filter, update, upsert).await
+                .map(|r| update_summary(&r)),
+            BulkOperation::UpdateMany { filter, update, upsert } => update_many(collection.clone(), filter, update, upsert).await
+                .map(|r| update_summary(&r)),
+            BulkOperation::ReplaceOne { filter, replacement, upsert } => replace_one(collection.clone(), filter, replacement, upsert).await
+                .map(|r| update_summary(&r)),
+            BulkOperation::DeleteOne(filter) => delete_one(collection.clone(), filter).await
+                .map(|r| mongodb::bson::doc! { "deleted_count": r.deleted_count as i64 }),
+            BulkOperation::DeleteMany(filter) => delete_many(collection.clone(), filter).await
+                .map(|r| mongodb::bson::doc! { "deleted_count": r.deleted_count as i64 }),
+        };
+
+        let mut entry = mongodb::bson::doc! { "index": index as i64, "operation": name };
+        let failed = outcome.is_err();
+        match outcome {
+            Ok(result) => entry.extend(result),
+            Err(e) => {
+                entry.insert("error", e.to_string());
+            }
+        }
+        results.push(entry);
+        if failed && ordered {
+            break;
+        }
+    }
+    results
+}
+
+fn update_summary(result: &mongodb::results::UpdateResult) -> Document {
+    mongodb::bson::doc! {
+        "matched_count": result.matched_count as i64,
+        "modified_count": result.modified_count as i64,
+        "upserted_id": result.upserted_id.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::test_support::scratch_database;
+    use mongodb::bson::{doc, Bson};
+    use futures::StreamExt;
+
+    #[test]
+    fn unclaimed_filter_keeps_the_callers_conditions() {
+        assert_eq!(
+            unclaimed_filter(doc! { "claimed_at": { "$lt": 5 } }, "claimed_at"),
+            doc! { "$and": [{ "claimed_at": { "$lt": 5 } }, { "claimed_at": { "$exists": false } }] }
+        );
+        assert_eq!(unclaimed_filter(Document::new(), "claimed_at"), doc! { "claimed_at": { "$exists": false } });
+    }
+
+    #[test]
+    fn duplicate_policy_parses_known_values() {
+        assert_eq!(DuplicatePolicy::parse("error"), Ok(DuplicatePolicy::Error));
+        assert_eq!(DuplicatePolicy::parse("skip"), Ok(DuplicatePolicy::Skip));
+        assert_eq!(DuplicatePolicy::parse("replace"), Ok(DuplicatePolicy::Replace));
+        assert!(DuplicatePolicy::parse("upsert").is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn duplicate_ids_follow_the_policy() {
+        let db = scratch_database().await;
+        let batch = || vec![doc! { "_id": 1, "v": "new" }, doc! { "_id": 2, "v": "new" }, doc! { "_id": 3, "v": "new" }];
+
+        for (policy, expected, stored) in [
+            (DuplicatePolicy::Error, (1, 0, 0, 1), "old"),
+            (DuplicatePolicy::Skip, (2, 1, 0, 0), "old"),
+            (DuplicatePolicy::Replace, (2, 0, 1, 0), "new"),
+        ] {
+            let coll = db.collection::<Document>(&format!("{:?}", policy));
+            coll.insert_one(doc! { "_id": 2, "v": "old" }, None).await.unwrap();
+
+            let result = insert_many_with_policy(coll.clone(), batch(), policy, false).await;
+            let counts = (
+                result.get_i64("inserted").unwrap(),
+                result.get_i64("skipped").unwrap(),
+                result.get_i64("replaced").unwrap(),
+                result.get_array("errors").unwrap().len() as i64,
+            );
+            assert_eq!(counts, expected, "{:?}", policy);
+            assert_eq!(coll.count_documents(None, None).await.unwrap(), 3, "{:?}", policy);
+
+            let existing = coll.find_one(doc! { "_id": 2 }, None).await.unwrap().unwrap();
+            assert_eq!(existing.get_str("v").unwrap(), stored, "{:?}", policy);
+        }
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn concurrent_dequeues_never_share_a_document() {
+        let db = scratch_database().await;
+        let jobs = db.collection::<Document>("jobs");
+        jobs.insert_many((0..20).map(|i| doc! { "_id": i, "queue": "mail" }), None).await.unwrap();
+
+        let workers = (0..4).map(|_| {
+            let jobs = jobs.clone();
+            tokio::spawn(async move {
+                let mut claimed = Vec::new();
+                while let Some(job) = dequeue(jobs.clone(), doc! { "queue": "mail" }, None, Some("claimed_at".to_string())).await.unwrap() {
+                    claimed.push(job.get_i32("_id").unwrap());
+                }
+                claimed
+            })
+        });
+        let mut claimed: Vec<i32> = futures::future::join_all(workers).await.into_iter().flat_map(|r| r.unwrap()).collect();
+        claimed.sort();
+        assert_eq!(claimed, (0..20).collect::<Vec<_>>());
+
+        assert_eq!(dequeue(jobs, Document::new(), None, None).await.unwrap().map(|d| d.get_i32("_id").unwrap()), Some(0));
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn ids_filter_accepts_extended_json_ids() {
+        let ids: Vec<mongodb::bson::Bson> = [serde_json::json!({ "$oid": "65f1c0ffee0000000000abcd" }), serde_json::json!(7)]
+            .into_iter()
+            .map(|id| crate::utils::json::extended_json_to_bson(id).unwrap())
+            .collect();
+        let oid = mongodb::bson::oid::ObjectId::parse_str("65f1c0ffee0000000000abcd").unwrap();
+        assert_eq!(ids_filter(ids).unwrap(), doc! { "_id": { "$in": [oid, 7] } });
+    }
+
+    #[test]
+    fn ids_filter_refuses_an_empty_selection() {
+        assert_eq!(ids_filter(Vec::new()).unwrap_err(), "At least one document id is required");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn update_by_ids_touches_only_the_selection() {
+        let db = scratch_database().await;
+        let items = db.collection::<Document>("items");
+        items.insert_many((0..6).map(|i| doc! { "_id": i, "flagged": false }), None).await.unwrap();
+
+        let filter = ids_filter(vec![1.into(), 3.into(), 4.into()]).unwrap();
+        let result = update_many(items.clone(), filter, doc! { "$set": { "flagged": true } }, None).await.unwrap();
+        assert_eq!((result.matched_count, result.modified_count), (3, 3));
+
+        let mut flagged: Vec<i32> = items
+            .distinct("_id", doc! { "flagged": true }, None)
+            .await
+            .unwrap()
+            .iter()
+            .filter_map(|id| id.as_i32())
+            .collect();
+        flagged.sort();
+        assert_eq!(flagged, vec![1, 3, 4]);
+        assert_eq!(items.count_documents(doc! { "flagged": false }, None).await.unwrap(), 3);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn delete_by_ids_keeps_the_rest() {
+        let db = scratch_database().await;
+        let items = db.collection::<Document>("items");
+        items.insert_many((0..5).map(|i| doc! { "_id": i }), None).await.unwrap();
+
+        let result = delete_many(items.clone(), ids_filter(vec![0.into(), 2.into(), 99.into()]).unwrap()).await.unwrap();
+        assert_eq!(result.deleted_count, 2);
+
+        let mut remaining: Vec<i32> = items.distinct("_id", None, None).await.unwrap().iter().filter_map(|id| id.as_i32()).collect();
+        remaining.sort();
+        assert_eq!(remaining, vec![1, 3, 4]);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn copy_collection_reshapes_through_the_transform() {
+        let db = scratch_database().await;
+        let source = db.collection::<Document>("people");
+        let destination = db.collection::<Document>("contacts");
+        source.insert_many((0..3).map(|i| doc! { "_id": i, "fullName": format!("Person {}", i), "internal": true }), None).await.unwrap();
+
+        let transform = vec![doc! { "$project": { "name": "$fullName" } }];
+        assert_eq!(copy_collection(source, destination.clone(), Some(transform)).await.unwrap(), 3);
+
+        let copied = destination.find_one(doc! { "_id": 1 }, None).await.unwrap().unwrap();
+        assert_eq!(copied, doc! { "_id": 1, "name": "Person 1" });
+        assert_eq!(destination.count_documents(None, None).await.unwrap(), 3);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn convert_field_type_stores_doubles_and_keeps_bad_values() {
+        let db = scratch_database().await;
+        let coll = db.collection::<Document>("products");
+        coll.insert_many(vec![
+            doc! { "_id": 1, "price": "9.99" },
+            doc! { "_id": 2, "price": "12" },
+            doc! { "_id": 3, "price": "call us" },
+        ], None).await.unwrap();
+
+        let result = convert_field_type(coll.clone(), "price", "double", Document::new()).await.unwrap();
+        assert_eq!(result.matched_count, 3);
+
+        let prices: Vec<Bson> = coll.find(None, None).await.unwrap()
+            .map(|d| d.unwrap().get("price").cloned().unwrap())
+            .collect().await;
+        assert_eq!(prices, vec![Bson::Double(9.99), Bson::Double(12.0), Bson::String("call us".to_string())]);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn convert_field_type_rejects_unsupported_targets() {
+        let coll = scratch_database().await.collection::<Document>("products");
+        let err = convert_field_type(coll.clone(), "price", "money", Document::new()).await.unwrap_err();
+        assert!(err.starts_with("Unsupported target type 'money'"), "{}", err);
+        assert_eq!(convert_field_type(coll, "$price", "double", Document::new()).await.unwrap_err(), "Invalid field name '$price'");
+    }
+
+    #[test]
+    fn bulk_operations_parse_the_shell_shape() {
+        let update = BulkOperation::parse(&doc! { "updateMany": { "filter": { "a": 1 }, "update": { "$set": { "b": 2 } }, "upsert": true } }).unwrap();
+        assert!(matches!(update, BulkOperation::UpdateMany { upsert: Some(true), .. }));
+        assert_eq!(BulkOperation::parse(&doc! { "deleteOne": { "filter": {} } }).unwrap().name(), "deleteOne");
+
+        assert_eq!(BulkOperation::parse(&doc! { "updateOne": { "filter": {} } }).unwrap_err(), "updateOne requires a 'update' object");
+        assert_eq!(BulkOperation::parse(&doc! { "upsertOne": {} }).unwrap_err(), "Unknown bulk operation 'upsertOne'");
+        assert!(BulkOperation::parse(&doc! { "insertOne": { "document": {} }, "deleteOne": { "filter": {} } }).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn bulk_preview_reports_would_affect_counts() {
+        let db = scratch_database().await;
+        let coll = db.collection::<Document>("orders");
+        coll.insert_many((0..10).map(|i| doc! { "_id": i, "status": if i < 6 { "open" } else { "closed" } }), None).await.unwrap();
+
+        let operations = vec![
+            BulkOperation::UpdateMany { filter: doc! { "status": "open" }, update: doc! { "$set": { "flag": true } }, upsert: None },
+            BulkOperation::UpdateOne { filter: doc! { "status": "missing" }, update: doc! { "$set": { "flag": true } }, upsert: Some(true) },
+            BulkOperation::DeleteMany(doc! { "status": "closed" }),
+        ];
+        let preview = preview_bulk(coll.clone(), &operations).await.unwrap();
+
+        assert_eq!(preview, vec![
+            doc! { "index": 0_i64, "operation": "updateMany", "would_match": 6_i64, "would_upsert": false },
+            doc! { "index": 1_i64, "operation": "updateOne", "would_match": 0_i64, "would_upsert": true },
+            doc! { "index": 2_i64, "operation": "deleteMany", "would_match": 4_i64 },
+        ]);
+        assert_eq!(coll.count_documents(None, None).await.unwrap(), 10);
+        assert_eq!(coll.count_documents(doc! { "flag": true }, None).await.unwrap(), 0);
+
+        db.drop(None).await.unwrap();
+    }
+}
@@ -1,28 +1,182 @@
 use mongodb::{Cursor, bson::Document};
 use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::mongo::aggregation::OutputTarget;
+
+const DEFAULT_BATCH_SIZE: usize = 50;
+pub const DEFAULT_TARGET_BATCH_BYTES: usize = 256 * 1024;
 
 pub struct CursorSession {
-    pub cursor: Cursor<Document>,
+    /// Locked for the duration of a fetch, so the session itself stays in the
+    /// session map (and can be cancelled or touched) while the cursor is awaited
+    pub cursor: Arc<tokio::sync::Mutex<Cursor<Document>>>,
+    /// Connection the cursor was opened on
+    pub connection_id: String,
     pub batch_size: usize,
+    /// When set, `batch_size` is re-tuned after every batch so that a batch
+    /// carries roughly this many bytes of BSON
+    pub target_batch_bytes: Option<usize>,
+    /// Client-side safety cap on the cumulative number of documents handed out,
+    /// independent of any server-side `limit`
+    pub max_total_documents: Option<usize>,
+    pub documents_returned: usize,
+    pub limit_reached: bool,
+    /// Last time the session was fetched from or touched, for idle tracking
+    pub last_accessed: Instant,
+    /// Namespace written by a terminal `$out` / `$merge`, for aggregation sessions
+    pub output_target: Option<OutputTarget>,
 }
 
 impl CursorSession {
-    pub async fn next_batch(&mut self) -> Vec<Document> {
-        let mut batch = Vec::with_capacity(self.batch_size);
-        for _ in 0..self.batch_size {
-            match self.cursor.next().await {
-                Some(Ok(doc)) => batch.push(doc),
-                Some(Err(_)) => {
-                    // Log error but continue with what we have
-                    break;
-                }
-                None => break,
+    pub fn new(cursor: Cursor<Document>, connection_id: String) -> Self {
+        CursorSession {
+            cursor: Arc::new(tokio::sync::Mutex::new(cursor)),
+            connection_id,
+            batch_size: DEFAULT_BATCH_SIZE,
+            target_batch_bytes: None,
+            max_total_documents: None,
+            documents_returned: 0,
+            limit_reached: false,
+            last_accessed: Instant::now(),
+            output_target: None,
+        }
+    }
+
+    /// How many documents the next batch may hold without passing `max_total_documents`
+    pub fn next_batch_limit(&self) -> usize {
+        batch_limit(self.batch_size, self.max_total_documents, self.documents_returned)
+    }
+
+    /// Account for a batch read by `read_batch`: re-tune the batch size when
+    /// adaptive batching is on and trip the guard once the cap is reached
+    pub fn record_batch(&mut self, documents: usize, batch_bytes: usize) {
+        self.touch();
+        if let Some(target) = self.target_batch_bytes {
+            if documents > 0 && batch_bytes > 0 {
+                self.set_batch_size(adapted_batch_size(target, batch_bytes, documents));
+            }
+        }
+
+        self.documents_returned += documents;
+        if let Some(max) = self.max_total_documents {
+            if self.documents_returned >= max {
+                self.limit_reached = true;
             }
         }
-        batch
     }
-    
+
+    pub fn touch(&mut self) {
+        self.last_accessed = Instant::now();
+    }
+
     pub fn set_batch_size(&mut self, size: usize) {
-        self.batch_size = size.max(1).min(1000); // Clamp between 1 and 1000
+        self.batch_size = size.clamp(1, 1000);
+    }
+
+    pub fn set_adaptive(&mut self, target_batch_bytes: Option<usize>) {
+        self.target_batch_bytes = target_batch_bytes.map(|bytes| bytes.max(1));
+    }
+}
+
+/// Read up to `limit` documents, also returning their BSON size when `measure_bytes`
+pub async fn read_batch(cursor: &mut Cursor<Document>, limit: usize, measure_bytes: bool) -> (Vec<Document>, usize) {
+    let mut batch = Vec::with_capacity(limit);
+    let mut batch_bytes = 0;
+    for _ in 0..limit {
+        match cursor.next().await {
+            Some(Ok(doc)) => {
+                if measure_bytes {
+                    batch_bytes += mongodb::bson::to_vec(&doc).map(|b| b.len()).unwrap_or(0);
+                }
+                batch.push(doc)
+            }
+            Some(Err(_)) => {
+                // Log error but continue with what we have
+                break;
+            }
+            None => break,
+        }
+    }
+    (batch, batch_bytes)
+}
+
+fn batch_limit(batch_size: usize, max_total_documents: Option<usize>, documents_returned: usize) -> usize {
+    match max_total_documents {
+        Some(max) => batch_size.min(max.saturating_sub(documents_returned)),
+        None => batch_size,
+    }
+}
+
+/// Batch size that makes a batch of documents like the last one weigh about `target_bytes`
+fn adapted_batch_size(target_bytes: usize, batch_bytes: usize, documents: usize) -> usize {
+    let average_doc_bytes = (batch_bytes / documents).max(1);
+    target_bytes / average_doc_bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_halts_at_the_cap() {
+        let mut returned = 0;
+        let mut batches = Vec::new();
+        loop {
+            let limit = batch_limit(50, Some(120), returned);
+            if limit == 0 {
+                break;
+            }
+            batches.push(limit);
+            returned += limit;
+        }
+        assert_eq!(batches, vec![50, 50, 20]);
+    }
+
+    #[test]
+    fn no_cap_keeps_the_batch_size() {
+        assert_eq!(batch_limit(50, None, 10_000), 50);
+    }
+
+    #[test]
+    fn adapted_batch_size_targets_the_payload() {
+        // Four 64KB documents fill the 256KB target
+        assert_eq!(adapted_batch_size(DEFAULT_TARGET_BATCH_BYTES, 50 * 64 * 1024, 50), 4);
+        assert_eq!(adapted_batch_size(DEFAULT_TARGET_BATCH_BYTES, 50 * 512, 50), 512);
+        // A document bigger than the target leaves nothing; `set_batch_size` clamps it to 1
+        assert_eq!(adapted_batch_size(DEFAULT_TARGET_BATCH_BYTES, 1024 * 1024, 1), 0);
+    }
+
+    /// Batch size after reading one batch from `documents` with adaptive batching on
+    async fn adapted_session_batch_size(documents: Vec<Document>) -> usize {
+        let db = crate::mongo::test_support::scratch_database().await;
+        let coll = db.collection::<Document>("items");
+        coll.insert_many(documents, None).await.unwrap();
+
+        let mut session = CursorSession::new(coll.find(None, None).await.unwrap(), "conn".to_string());
+        session.set_adaptive(Some(DEFAULT_TARGET_BATCH_BYTES));
+        let cursor = Arc::clone(&session.cursor);
+        let (batch, bytes) = read_batch(&mut *cursor.lock().await, session.next_batch_limit(), true).await;
+        session.record_batch(batch.len(), bytes);
+
+        db.drop(None).await.unwrap();
+        session.batch_size
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn adaptive_batches_shrink_for_large_documents() {
+        let padding = "x".repeat(100 * 1024);
+        let documents = (0..60).map(|i| mongodb::bson::doc! { "_id": i, "padding": &padding }).collect();
+        let batch_size = adapted_session_batch_size(documents).await;
+        assert!((1..DEFAULT_BATCH_SIZE).contains(&batch_size), "{}", batch_size);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn adaptive_batches_grow_for_tiny_documents() {
+        let documents = (0..60).map(|i| mongodb::bson::doc! { "_id": i }).collect();
+        assert_eq!(adapted_session_batch_size(documents).await, 1000);
     }
 }
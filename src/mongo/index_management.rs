@@ -19,6 +19,39 @@ pub async fn create_index(
     Ok(index_name)
 }
 
+const PARTIAL_FILTER_OPERATORS: &[&str] = &["$eq", "$gt", "$gte", "$lt", "$lte", "$type", "$exists"];
+
+/// Check a `partialFilterExpression` only uses what the server accepts: equality,
+/// the comparison operators, `$type`, `$exists` and `$and`
+pub fn validate_partial_filter(filter: &Document) -> Result<(), String> {
+    for (key, value) in filter {
+        if key == "$and" {
+            let clauses = value.as_array()
+                .ok_or_else(|| "Partial filter '$and' must be an array".to_string())?;
+            for clause in clauses {
+                let clause = clause.as_document()
+                    .ok_or_else(|| "Partial filter '$and' entries must be objects".to_string())?;
+                validate_partial_filter(clause)?;
+            }
+        } else if key.starts_with('$') {
+            return Err(format!(
+                "Partial filter operator '{}' is not supported; use field conditions combined with $and",
+                key
+            ));
+        } else if let Some(condition) = value.as_document() {
+            for operator in condition.keys().filter(|op| op.starts_with('$')) {
+                if !PARTIAL_FILTER_OPERATORS.contains(&operator.as_str()) {
+                    return Err(format!(
+                        "Partial filter operator '{}' on '{}' is not supported; allowed: {}",
+                        operator, key, PARTIAL_FILTER_OPERATORS.join(", ")
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 pub async fn create_index_with_options(
     collection: Collection<Document>,
     keys: Document,
@@ -30,7 +63,11 @@ pub async fn create_index_with_options(
     partial_filter: Option<Document>,
     text_index_version: Option<i32>,
     default_language: Option<String>,
-) -> mongodb::error::Result<String> {
+) -> Result<String, String> {
+    if let Some(filter) = &partial_filter {
+        validate_partial_filter(filter)?;
+    }
+
     let mut index_options = IndexOptions::default();
     
     if let Some(name_val) = name {
@@ -72,11 +109,64 @@ pub async fn create_index_with_options(
     
     let index_name = collection
         .create_index(index_model, None)
-        .await?;
+        .await
+        .map_err(|e| e.to_string())?;
     
     Ok(index_name)
 }
 
+pub fn has_hashed_key(keys: &Document) -> bool {
+    keys.values().any(|value| value.as_str() == Some("hashed"))
+}
+
+/// Enforce the server's hashed index rules up front: one hashed field per index,
+/// no uniqueness, and compound hashed indexes only from MongoDB 4.4
+pub fn validate_hashed_keys(keys: &Document, unique: bool, server_version: (u32, u32)) -> Result<(), String> {
+    let hashed_count = keys.values().filter(|value| value.as_str() == Some("hashed")).count();
+    if hashed_count == 0 {
+        return Ok(());
+    }
+    if hashed_count > 1 {
+        return Err("A hashed index can only hash a single field".to_string());
+    }
+    if unique {
+        return Err("Hashed indexes cannot be unique".to_string());
+    }
+    if keys.len() > 1 && server_version < (4, 4) {
+        return Err(format!(
+            "Compound hashed indexes require MongoDB 4.4 or newer (server is {}.{})",
+            server_version.0, server_version.1
+        ));
+    }
+    Ok(())
+}
+
+/// Build an `IndexModel` from a `{keys, options}` spec. Options use the server's
+/// `createIndexes` names (`name`, `unique`, `expireAfterSeconds`, `partialFilterExpression`, ...).
+pub fn index_model_from_spec(keys: Document, options: Option<Document>) -> Result<IndexModel, String> {
+    if keys.is_empty() {
+        return Err("Index keys cannot be empty".to_string());
+    }
+    if let Some(filter) = options.as_ref().and_then(|o| o.get_document("partialFilterExpression").ok()) {
+        validate_partial_filter(filter)?;
+    }
+    let options = options
+        .map(mongodb::bson::from_document::<IndexOptions>)
+        .transpose()
+        .map_err(|e| format!("Invalid index options: {}", e))?;
+
+    Ok(IndexModel::builder().keys(keys).options(options).build())
+}
+
+/// Create several indexes with a single `createIndexes` command, returning their names
+pub async fn create_indexes(
+    collection: Collection<Document>,
+    models: Vec<IndexModel>,
+) -> mongodb::error::Result<Vec<String>> {
+    let result = collection.create_indexes(models, None).await?;
+    Ok(result.index_names)
+}
+
 pub async fn drop_index(
     collection: Collection<Document>,
     index_name: String,
@@ -95,7 +185,7 @@ pub async fn drop_all_indexes(
 pub async fn rebuild_indexes(
     collection: Collection<Document>,
 ) -> mongodb::error::Result<()> {
-    let db = collection.database();
+    let db = collection.client().database(&collection.namespace().db);
     let coll_name = collection.name();
     
     db.run_command(
@@ -129,7 +219,7 @@ pub async fn get_index_usage_stats(
 pub async fn analyze_index_usage(
     collection: Collection<Document>,
 ) -> mongodb::error::Result<Vec<Document>> {
-    let db = collection.database();
+    let db = collection.client().database(&collection.namespace().db);
     let coll_name = collection.name();
     
     let stats = get_index_usage_stats(db, coll_name.to_string()).await?;
@@ -139,7 +229,7 @@ pub async fn analyze_index_usage(
         if let Some(first_batch) = cursor_doc.get_array("firstBatch").ok() {
             let mut results = Vec::new();
             for item in first_batch {
-                if let Ok(doc) = item.as_document() {
+                if let Some(doc) = item.as_document() {
                     results.push(doc.clone());
                 }
             }
@@ -150,6 +240,55 @@ pub async fn analyze_index_usage(
     Ok(Vec::new())
 }
 
+/// Every non-`_id_` index in the database with no recorded accesses since its
+/// `$indexStats` counters were reset, with its size. Views and collections where
+/// `$indexStats` fails are skipped.
+pub async fn find_unused_indexes(
+    database: Database,
+) -> mongodb::error::Result<Vec<Document>> {
+    let collection_names = database
+        .list_collection_names(mongodb::bson::doc! { "type": "collection" })
+        .await?;
+
+    let mut unused = Vec::new();
+    for collection_name in collection_names {
+        let collection = database.collection::<Document>(&collection_name);
+        let usage = match analyze_index_usage(collection.clone()).await {
+            Ok(usage) => usage,
+            Err(_) => continue,
+        };
+
+        let stats = crate::mongo::performance::get_collection_stats(collection).await.ok();
+        let index_sizes = stats.as_ref().and_then(|s| s.get_document("indexSizes").ok());
+
+        for index in usage {
+            let name = index.get_str("name").unwrap_or_default();
+            if name == "_id_" {
+                continue;
+            }
+            let accesses = index.get_document("accesses").ok();
+            let ops = accesses.and_then(|a| a.get("ops")).and_then(|ops| match ops {
+                mongodb::bson::Bson::Int32(n) => Some(*n as i64),
+                mongodb::bson::Bson::Int64(n) => Some(*n),
+                _ => None,
+            });
+            if ops != Some(0) {
+                continue;
+            }
+
+            unused.push(mongodb::bson::doc! {
+                "collection": &collection_name,
+                "name": name,
+                "key": index.get_document("key").cloned().unwrap_or_default(),
+                "since": accesses.and_then(|a| a.get("since")).cloned(),
+                "size": index_sizes.and_then(|sizes| sizes.get(name)).cloned(),
+            });
+        }
+    }
+
+    Ok(unused)
+}
+
 pub async fn get_index_recommendations(
     collection: Collection<Document>,
     sample_size: Option<usize>,
@@ -180,3 +319,451 @@ pub async fn get_index_recommendations(
     Ok(recommendations)
 }
 
+fn collect_filter_fields(filter: &Document, equality: &mut Vec<String>, range: &mut Vec<String>) {
+    for (key, value) in filter {
+        if key == "$and" {
+            if let Ok(clauses) = filter.get_array("$and") {
+                for clause in clauses.iter().filter_map(|c| c.as_document()) {
+                    collect_filter_fields(clause, equality, range);
+                }
+            }
+            continue;
+        }
+        if key.starts_with('$') {
+            continue;
+        }
+
+        let is_equality = match value {
+            mongodb::bson::Bson::Document(ops) => ops.keys().all(|op| op == "$eq" || op == "$in")
+                || !ops.keys().any(|op| op.starts_with('$')),
+            _ => true,
+        };
+        let target = if is_equality { &mut *equality } else { &mut *range };
+        if !target.contains(key) {
+            target.push(key.clone());
+        }
+    }
+}
+
+/// Top-level query operators `collect_filter_fields` does not look inside
+/// (`$or`, `$nor`, `$expr`, `$where`, ...), searching through `$and` clauses
+fn unhandled_operators(filter: &Document) -> Vec<String> {
+    let mut operators = Vec::new();
+    for (key, value) in filter {
+        if key == "$and" {
+            for clause in value.as_array().into_iter().flatten().filter_map(|c| c.as_document()) {
+                for operator in unhandled_operators(clause) {
+                    if !operators.contains(&operator) {
+                        operators.push(operator);
+                    }
+                }
+            }
+        } else if key.starts_with('$') && key != "$comment" && !operators.contains(key) {
+            operators.push(key.clone());
+        }
+    }
+    operators
+}
+
+/// Suggest a compound index for a find following the equality, sort, range rule
+pub fn suggest_index(filter: &Document, sort: Option<&Document>) -> Document {
+    let (mut equality, mut range) = (Vec::new(), Vec::new());
+    collect_filter_fields(filter, &mut equality, &mut range);
+
+    let mut keys = Document::new();
+    for field in &equality {
+        keys.insert(field.clone(), 1);
+    }
+    for (field, direction) in sort.into_iter().flatten() {
+        if !keys.contains_key(field) {
+            keys.insert(field.clone(), direction.clone());
+        }
+    }
+    for field in &range {
+        if !keys.contains_key(field) {
+            keys.insert(field.clone(), 1);
+        }
+    }
+    keys
+}
+
+/// Suggest a projection that lets `filter` be answered from an index alone (no document fetch).
+/// Picks the smallest ascending/descending index whose keys contain every filter field,
+/// preferring one where the filter fields form a key prefix. Multikey indexes cannot cover
+/// queries on array fields, which only an explain can confirm.
+pub async fn suggest_covered_projection(
+    collection: Collection<Document>,
+    filter: &Document,
+) -> mongodb::error::Result<Document> {
+    let operators = unhandled_operators(filter);
+    if !operators.is_empty() {
+        return Ok(mongodb::bson::doc! {
+            "coverable": false,
+            "index": mongodb::bson::Bson::Null,
+            "projection": mongodb::bson::Bson::Null,
+            "reason": format!("Fields referenced under {} cannot be checked against the indexes", operators.join(", ")),
+        });
+    }
+
+    let (mut fields, mut range) = (Vec::new(), Vec::new());
+    collect_filter_fields(filter, &mut fields, &mut range);
+    fields.append(&mut range);
+
+    let indexes = crate::mongo::index::list_indexes(collection).await?;
+
+    let best = indexes
+        .iter()
+        .filter_map(|index| {
+            let keys = index.get_document("key").ok()?;
+            // Hashed, text and geo keys store transformed values and cannot cover a query
+            let plain = keys.values().all(|v| matches!(v, mongodb::bson::Bson::Int32(_) | mongodb::bson::Bson::Int64(_) | mongodb::bson::Bson::Double(_)));
+            if !plain || !fields.iter().all(|f| keys.contains_key(f)) {
+                return None;
+            }
+            let prefix = keys.keys().take(fields.len()).all(|k| fields.contains(k));
+            Some((index, keys, prefix))
+        })
+        .min_by_key(|(_, keys, prefix)| (!prefix, keys.len()));
+
+    match best {
+        Some((index, keys, _)) => {
+            let mut projection = Document::new();
+            for key in keys.keys() {
+                projection.insert(key.clone(), 1);
+            }
+            if !keys.contains_key("_id") {
+                projection.insert("_id", 0);
+            }
+            Ok(mongodb::bson::doc! {
+                "coverable": true,
+                "index": index.get_str("name").unwrap_or_default(),
+                "projection": projection,
+            })
+        }
+        None => Ok(mongodb::bson::doc! {
+            "coverable": false,
+            "index": mongodb::bson::Bson::Null,
+            "projection": mongodb::bson::Bson::Null,
+            "reason": if fields.is_empty() {
+                "The filter has no fields an index could match"
+            } else {
+                "No index contains every filter field"
+            },
+        }),
+    }
+}
+
+/// Recreate every index of `source` (except `_id_`) on `destination`,
+/// preserving name, uniqueness, sparseness, TTL, partial filter and text options
+pub async fn copy_indexes(
+    source: Collection<Document>,
+    destination: Collection<Document>,
+) -> Result<Vec<String>, String> {
+    let indexes = crate::mongo::index::list_indexes(source).await.map_err(|e| e.to_string())?;
+
+    let mut created = Vec::new();
+    for index in indexes {
+        let name = index.get_str("name").unwrap_or("").to_string();
+        if name == "_id_" {
+            continue;
+        }
+        let keys = match index.get_document("key") {
+            Ok(keys) => keys.clone(),
+            Err(_) => continue,
+        };
+
+        let expire_after_seconds = index.get("expireAfterSeconds").and_then(|v| match v {
+            mongodb::bson::Bson::Int32(n) => Some(*n as i64),
+            mongodb::bson::Bson::Int64(n) => Some(*n),
+            mongodb::bson::Bson::Double(n) => Some(*n as i64),
+            _ => None,
+        });
+
+        let index_name = create_index_with_options(
+            destination.clone(),
+            keys,
+            Some(name),
+            index.get_bool("unique").ok(),
+            index.get_bool("sparse").ok(),
+            None,
+            expire_after_seconds,
+            index.get_document("partialFilterExpression").ok().cloned(),
+            index.get_i32("textIndexVersion").ok(),
+            index.get_str("default_language").ok().map(|s| s.to_string()),
+        ).await?;
+
+        created.push(index_name);
+    }
+
+    Ok(created)
+}
+
+/// Explain a find before and after building a candidate index, then drop the index.
+/// The index is built hidden and only unhidden for the second explain, since the
+/// planner ignores hidden indexes; it is dropped even if the re-explain fails.
+pub async fn compare_index_impact(
+    collection: Collection<Document>,
+    filter: Document,
+    candidate_keys: Document,
+) -> mongodb::error::Result<Document> {
+    let database = collection.client().database(&collection.namespace().db);
+    let before = crate::mongo::performance::explain_find(collection.clone(), filter.clone()).await?;
+
+    let mut options = IndexOptions::default();
+    options.name = Some(format!("novadb_candidate_{}", mongodb::bson::oid::ObjectId::new().to_hex()));
+    options.hidden = Some(true);
+    let index_name = create_index(collection.clone(), candidate_keys, Some(options)).await?;
+
+    let after = async {
+        database.run_command(
+            mongodb::bson::doc! {
+                "collMod": collection.name(),
+                "index": { "name": &index_name, "hidden": false },
+            },
+            None,
+        ).await?;
+        crate::mongo::performance::explain_find(collection.clone(), filter).await
+    }.await;
+
+    let dropped = drop_index(collection, index_name).await;
+    let after = after?;
+    dropped?;
+
+    let summary_before = crate::mongo::performance::summarize_plan(&before);
+    let summary_after = crate::mongo::performance::summarize_plan(&after);
+    let examined_before = summary_before.docs_examined.unwrap_or(0);
+    let examined_after = summary_after.docs_examined.unwrap_or(0);
+
+    Ok(mongodb::bson::doc! {
+        "before": {
+            "summary": mongodb::bson::to_bson(&summary_before)?,
+            "docs_examined": examined_before,
+            "plan": before,
+        },
+        "after": {
+            "summary": mongodb::bson::to_bson(&summary_after)?,
+            "docs_examined": examined_after,
+            "plan": after,
+        },
+        "docs_examined_delta": examined_after - examined_before,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::test_support::scratch_database;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn filter_fields_split_equality_and_range() {
+        let (mut equality, mut range) = (Vec::new(), Vec::new());
+        let filter = doc! { "status": "active", "$and": [{ "age": { "$gte": 18 } }, { "tag": { "$in": ["a"] } }] };
+        collect_filter_fields(&filter, &mut equality, &mut range);
+        assert_eq!(equality, vec!["status", "tag"]);
+        assert_eq!(range, vec!["age"]);
+    }
+
+    #[test]
+    fn suggested_index_follows_equality_sort_range() {
+        let filter = doc! { "total": { "$gt": 10 }, "status": 1 };
+        let keys = suggest_index(&filter, Some(&doc! { "created": -1, "status": 1 }));
+        assert_eq!(keys, doc! { "status": 1, "created": -1, "total": 1 });
+        assert_eq!(keys.to_string(), r#"{ "status": 1, "created": -1, "total": 1 }"#);
+        assert!(suggest_index(&Document::new(), None).is_empty());
+    }
+
+    #[test]
+    fn unhandled_operators_are_found_inside_and() {
+        let filter = doc! {
+            "status": "active",
+            "$or": [{ "a": 1 }, { "b": 2 }],
+            "$and": [{ "$expr": { "$gt": ["$a", "$b"] } }, { "$or": [{ "c": 1 }] }],
+            "$comment": "dashboard",
+        };
+        assert_eq!(unhandled_operators(&filter), vec!["$or", "$expr"]);
+    }
+
+    #[test]
+    fn plain_filters_have_no_unhandled_operators() {
+        let filter = doc! { "status": "active", "$and": [{ "age": { "$gte": 18 } }] };
+        assert!(unhandled_operators(&filter).is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn only_indexes_without_accesses_are_unused() {
+        use mongodb::{IndexModel, options::FindOptions};
+
+        let db = scratch_database().await;
+        let orders = db.collection::<Document>("orders");
+        let users = db.collection::<Document>("users");
+        orders.insert_one(doc! { "status": "open" }, None).await.unwrap();
+        users.insert_one(doc! { "email": "a@example.com" }, None).await.unwrap();
+        orders.create_index(IndexModel::builder().keys(doc! { "status": 1 }).build(), None).await.unwrap();
+        users.create_index(IndexModel::builder().keys(doc! { "email": 1 }).build(), None).await.unwrap();
+
+        // Only the orders index sees a query
+        let hinted = FindOptions::builder().hint(mongodb::options::Hint::Name("status_1".to_string())).build();
+        orders.find(doc! { "status": "open" }, hinted).await.unwrap();
+
+        let unused = find_unused_indexes(db.clone()).await.unwrap();
+        let names: Vec<(&str, &str)> = unused
+            .iter()
+            .map(|index| (index.get_str("collection").unwrap(), index.get_str("name").unwrap()))
+            .collect();
+        assert_eq!(names, vec![("users", "email_1")]);
+        assert!(unused[0].get("size").is_some_and(|size| !matches!(size, mongodb::bson::Bson::Null)));
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn copied_indexes_keep_their_options() {
+        let db = scratch_database().await;
+        let source = db.collection::<Document>("source");
+        let destination = db.collection::<Document>("destination");
+        let unique = IndexOptions::builder().name("email_unique".to_string()).unique(true).sparse(true).build();
+        let ttl = IndexOptions::builder()
+            .expire_after(std::time::Duration::from_secs(3600))
+            .partial_filter_expression(doc! { "archived": true })
+            .build();
+        source.create_index(IndexModel::builder().keys(doc! { "email": 1 }).options(unique).build(), None).await.unwrap();
+        source.create_index(IndexModel::builder().keys(doc! { "created_at": 1 }).options(ttl).build(), None).await.unwrap();
+
+        let mut created = copy_indexes(source.clone(), destination.clone()).await.unwrap();
+        created.sort();
+        assert_eq!(created, vec!["created_at_1", "email_unique"]);
+
+        // Every option the source reports, except the namespace, survives the copy
+        let without_ns = |mut indexes: Vec<Document>| {
+            indexes.iter_mut().for_each(|index| { index.remove("ns"); });
+            indexes.sort_by_key(|index| index.get_str("name").unwrap_or_default().to_string());
+            indexes
+        };
+        let copied = without_ns(crate::mongo::index::list_indexes(destination).await.unwrap());
+        assert_eq!(copied, without_ns(crate::mongo::index::list_indexes(source).await.unwrap()));
+        let names: Vec<&str> = copied.iter().map(|index| index.get_str("name").unwrap()).collect();
+        assert_eq!(names, vec!["_id_", "created_at_1", "email_unique"]);
+        assert!(copied[1].contains_key("expireAfterSeconds") && copied[1].contains_key("partialFilterExpression"));
+        assert_eq!(copied[2].get_bool("unique"), Ok(true));
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB 4.4+ server at MONGODB_TEST_URI"]
+    async fn matching_candidate_index_cuts_docs_examined() {
+        let db = scratch_database().await;
+        let orders = db.collection::<Document>("orders");
+        orders.insert_many((0..200).map(|i| doc! { "customer": i % 50, "total": i }), None).await.unwrap();
+
+        let impact = compare_index_impact(orders.clone(), doc! { "customer": 7 }, doc! { "customer": 1 }).await.unwrap();
+        assert_eq!(impact.get_document("before").unwrap().get_i64("docs_examined"), Ok(200));
+        assert_eq!(impact.get_document("after").unwrap().get_i64("docs_examined"), Ok(4));
+        assert_eq!(impact.get_i64("docs_examined_delta"), Ok(-196));
+
+        // Only `_id_` is left behind
+        assert_eq!(crate::mongo::index::list_indexes(orders).await.unwrap().len(), 1);
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn index_specs_keep_key_order_and_options() {
+        let model = index_model_from_spec(
+            doc! { "status": 1, "created": -1, "total": 1 },
+            Some(doc! { "name": "status_created", "unique": true, "expireAfterSeconds": 60 }),
+        ).unwrap();
+        assert_eq!(model.keys.keys().collect::<Vec<_>>(), vec!["status", "created", "total"]);
+        let options = model.options.unwrap();
+        assert_eq!(options.name.as_deref(), Some("status_created"));
+        assert_eq!(options.unique, Some(true));
+        assert_eq!(options.expire_after, Some(std::time::Duration::from_secs(60)));
+
+        assert_eq!(index_model_from_spec(Document::new(), None).unwrap_err(), "Index keys cannot be empty");
+        assert!(index_model_from_spec(doc! { "a": 1 }, Some(doc! { "unique": "yes" })).unwrap_err().starts_with("Invalid index options"));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn create_indexes_builds_all_specs_at_once() {
+        let db = scratch_database().await;
+        let coll = db.collection::<Document>("orders");
+        let models = vec![
+            index_model_from_spec(doc! { "status": 1 }, None).unwrap(),
+            index_model_from_spec(doc! { "customer": 1, "created": -1 }, None).unwrap(),
+            index_model_from_spec(doc! { "sku": 1 }, Some(doc! { "name": "by_sku", "unique": true })).unwrap(),
+        ];
+
+        let names = create_indexes(coll.clone(), models).await.unwrap();
+        assert_eq!(names, vec!["status_1", "customer_1_created_-1", "by_sku"]);
+
+        let mut existing = coll.list_index_names().await.unwrap();
+        existing.sort();
+        assert_eq!(existing, vec!["_id_", "by_sku", "customer_1_created_-1", "status_1"]);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn hashed_keys_follow_the_server_rules() {
+        assert!(!has_hashed_key(&doc! { "a": 1 }));
+        assert!(has_hashed_key(&doc! { "a": "hashed" }));
+
+        assert!(validate_hashed_keys(&doc! { "a": "hashed" }, false, (4, 2)).is_ok());
+        assert!(validate_hashed_keys(&doc! { "region": 1, "a": "hashed" }, false, (4, 4)).is_ok());
+        assert_eq!(
+            validate_hashed_keys(&doc! { "region": 1, "a": "hashed" }, false, (4, 2)).unwrap_err(),
+            "Compound hashed indexes require MongoDB 4.4 or newer (server is 4.2)"
+        );
+        assert_eq!(
+            validate_hashed_keys(&doc! { "a": "hashed", "b": "hashed" }, false, (7, 0)).unwrap_err(),
+            "A hashed index can only hash a single field"
+        );
+        assert_eq!(validate_hashed_keys(&doc! { "a": "hashed" }, true, (7, 0)).unwrap_err(), "Hashed indexes cannot be unique");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn hashed_indexes_list_with_their_type() {
+        let db = scratch_database().await;
+        let coll = db.collection::<Document>("users");
+        coll.insert_one(doc! { "tenant": "acme" }, None).await.unwrap();
+
+        let name = create_index_with_options(coll.clone(), doc! { "tenant": "hashed" }, None, None, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(name, "tenant_hashed");
+
+        let indexes = crate::mongo::index::list_indexes(coll).await.unwrap();
+        let hashed = indexes.iter().find(|index| index.get_str("name") == Ok("tenant_hashed")).unwrap();
+        assert_eq!(hashed.get_document("key").unwrap(), &doc! { "tenant": "hashed" });
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn partial_filters_accept_supported_operators() {
+        let filter = doc! {
+            "status": "active",
+            "$and": [
+                { "total": { "$gte": 100, "$lt": 1000 } },
+                { "email": { "$exists": true, "$type": "string" } },
+            ],
+        };
+        assert!(validate_partial_filter(&filter).is_ok());
+    }
+
+    #[test]
+    fn partial_filters_reject_unsupported_operators() {
+        let or_filter = doc! { "$or": [{ "status": "active" }, { "status": "trial" }] };
+        assert_eq!(
+            validate_partial_filter(&or_filter).unwrap_err(),
+            "Partial filter operator '$or' is not supported; use field conditions combined with $and"
+        );
+
+        let nested = doc! { "$and": [{ "status": { "$in": ["active", "trial"] } }] };
+        assert!(validate_partial_filter(&nested).unwrap_err().starts_with("Partial filter operator '$in' on 'status' is not supported"));
+    }
+}
@@ -7,3 +7,7 @@ pub mod crud;
 pub mod performance;
 pub mod change_streams;
 pub mod index_management;
+pub mod schema;
+pub mod admin;
+#[cfg(test)]
+pub mod test_support;
@@ -1,18 +1,154 @@
-use mongodb::{Collection, bson::Document};
+use mongodb::{Collection, Database, bson::{Bson, Document}};
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const EXPLAIN_CACHE_CAPACITY: usize = 64;
+const EXPLAIN_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Recently computed explain results keyed by namespace and query shape, least recently used first
+#[derive(Default)]
+struct ExplainCache {
+    entries: HashMap<String, (Instant, Document)>,
+    order: VecDeque<String>,
+}
+
+static EXPLAIN_CACHE: OnceLock<Mutex<ExplainCache>> = OnceLock::new();
+
+fn explain_cache() -> &'static Mutex<ExplainCache> {
+    EXPLAIN_CACHE.get_or_init(|| Mutex::new(ExplainCache::default()))
+}
+
+/// Replace literal values with placeholders, keeping field names and operators,
+/// so `{age: {$gt: 20}}` and `{age: {$gt: 30}}` have the same shape
+pub fn query_shape(value: &Bson) -> Bson {
+    match value {
+        Bson::Document(doc) => Bson::Document(
+            doc.iter().map(|(key, value)| (key.clone(), query_shape(value))).collect()
+        ),
+        // Arrays of literals (e.g. `$in` lists) collapse to one placeholder regardless of length
+        Bson::Array(items) if items.iter().all(|item| !matches!(item, Bson::Document(_) | Bson::Array(_))) => {
+            Bson::String("?".to_string())
+        }
+        Bson::Array(items) => Bson::Array(items.iter().map(query_shape).collect()),
+        // Field paths and variables are part of the shape, not literals
+        Bson::String(s) if s.starts_with('$') => value.clone(),
+        _ => Bson::String("?".to_string()),
+    }
+}
+
+pub fn explain_cache_key(namespace: &str, query_type: &str, query: &Bson) -> String {
+    format!("{}|{}|{}", namespace, query_type, query_shape(query))
+}
+
+impl ExplainCache {
+    fn get(&mut self, key: &str) -> Option<Document> {
+        let fresh = match self.entries.get(key) {
+            Some((stored_at, _)) => stored_at.elapsed() < EXPLAIN_CACHE_TTL,
+            None => return None,
+        };
+        self.order.retain(|k| k != key);
+        if !fresh {
+            self.entries.remove(key);
+            return None;
+        }
+        self.order.push_back(key.to_string());
+        self.entries.get(key).map(|(_, explain)| explain.clone())
+    }
+
+    fn store(&mut self, key: String, explain: Document) {
+        self.order.retain(|k| k != &key);
+        self.order.push_back(key.clone());
+        self.entries.insert(key, (Instant::now(), explain));
+        while self.order.len() > EXPLAIN_CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+pub fn get_cached_explain(key: &str) -> Option<Document> {
+    explain_cache().lock().ok()?.get(key)
+}
+
+pub fn store_explain(key: String, explain: Document) {
+    if let Ok(mut cache) = explain_cache().lock() {
+        cache.store(key, explain);
+    }
+}
 
 pub async fn explain_find(
     collection: Collection<Document>,
     filter: Document,
 ) -> mongodb::error::Result<Document> {
-    let db = collection.database();
+    run_find_explain(collection, filter, None, None, "executionStats").await
+}
+
+/// Plan-only explain for a find; the query is not executed again
+pub async fn explain_find_plan(
+    collection: Collection<Document>,
+    filter: Document,
+    sort: Option<Document>,
+) -> mongodb::error::Result<Document> {
+    run_find_explain(collection, filter, sort, None, "queryPlanner").await
+}
+
+/// Plan-only explain including a projection, which decides whether an index can cover the query
+pub async fn explain_find_projected_plan(
+    collection: Collection<Document>,
+    filter: Document,
+    sort: Option<Document>,
+    projection: Option<Document>,
+) -> mongodb::error::Result<Document> {
+    run_find_explain(collection, filter, sort, projection, "queryPlanner").await
+}
+
+async fn run_find_explain(
+    collection: Collection<Document>,
+    filter: Document,
+    sort: Option<Document>,
+    projection: Option<Document>,
+    verbosity: &str,
+) -> mongodb::error::Result<Document> {
+    let db = collection.client().database(&collection.namespace().db);
     let coll_name = collection.name();
+
+    let mut find = mongodb::bson::doc! {
+        "find": coll_name,
+        "filter": filter
+    };
+    if let Some(sort) = sort {
+        find.insert("sort", sort);
+    }
+    if let Some(projection) = projection {
+        find.insert("projection", projection);
+    }
     
     // Use explain command directly
     db.run_command(
         mongodb::bson::doc! {
-            "explain": mongodb::bson::doc! {
-                "find": coll_name,
-                "filter": filter
+            "explain": find,
+            "verbosity": verbosity
+        },
+        None,
+    ).await
+}
+
+/// Explain a count, showing whether it is answered by an index-only `COUNT_SCAN`
+pub async fn explain_count(
+    collection: Collection<Document>,
+    filter: Document,
+) -> mongodb::error::Result<Document> {
+    let db = collection.client().database(&collection.namespace().db);
+
+    db.run_command(
+        mongodb::bson::doc! {
+            "explain": {
+                "count": collection.name(),
+                "query": filter
             },
             "verbosity": "executionStats"
         },
@@ -23,28 +159,155 @@ pub async fn explain_find(
 pub async fn explain_aggregate(
     collection: Collection<Document>,
     pipeline: Vec<Document>,
+    allow_disk_use: Option<bool>,
+) -> mongodb::error::Result<Document> {
+    run_aggregate_explain(collection, pipeline, allow_disk_use, "executionStats").await
+}
+
+/// Plan-only explain for a pipeline; nothing is executed on the server
+pub async fn explain_aggregate_plan(
+    collection: Collection<Document>,
+    pipeline: Vec<Document>,
 ) -> mongodb::error::Result<Document> {
-    let db = collection.database();
+    run_aggregate_explain(collection, pipeline, None, "queryPlanner").await
+}
+
+async fn run_aggregate_explain(
+    collection: Collection<Document>,
+    pipeline: Vec<Document>,
+    allow_disk_use: Option<bool>,
+    verbosity: &str,
+) -> mongodb::error::Result<Document> {
+    let db = collection.client().database(&collection.namespace().db);
     let coll_name = collection.name();
+
+    let mut aggregate = mongodb::bson::doc! {
+        "aggregate": coll_name,
+        "pipeline": pipeline,
+        "cursor": mongodb::bson::doc! {}
+    };
+    // Explain with the same disk settings as the real run, or spilling stages fail differently
+    if let Some(allow) = allow_disk_use {
+        aggregate.insert("allowDiskUse", allow);
+    }
     
     // Use explain command directly
     db.run_command(
         mongodb::bson::doc! {
-            "explain": mongodb::bson::doc! {
-                "aggregate": coll_name,
-                "pipeline": pipeline,
-                "cursor": mongodb::bson::doc! {}
-            },
-            "verbosity": "executionStats"
+            "explain": aggregate,
+            "verbosity": verbosity
         },
         None,
     ).await
 }
 
+/// The parts of a find or aggregate explain the plan panel shows, in one shape for both
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExplainSummary {
+    /// Root stage of the winning plan
+    pub stage: Option<String>,
+    pub stages: Vec<String>,
+    /// First index used by the winning plan
+    pub index: Option<String>,
+    pub indexes: Vec<String>,
+    pub collection_scan: bool,
+    pub docs_examined: Option<i64>,
+    pub keys_examined: Option<i64>,
+    pub returned: Option<i64>,
+    pub millis: Option<i64>,
+}
+
+impl ExplainSummary {
+    /// `collection_scan`, `covered` (index only, no FETCH), `index_scan`, or `other` (e.g. EOF)
+    pub fn coverage(&self) -> &'static str {
+        let has_stage = |name: &str| self.stages.iter().any(|stage| stage == name);
+        if self.collection_scan {
+            "collection_scan"
+        } else if self.indexes.is_empty() && !has_stage("IDHACK") {
+            "other"
+        } else if has_stage("FETCH") || has_stage("IDHACK") {
+            "index_scan"
+        } else {
+            "covered"
+        }
+    }
+}
+
+fn get_number(doc: &Document, key: &str) -> Option<i64> {
+    match doc.get(key)? {
+        Bson::Int32(n) => Some(*n as i64),
+        Bson::Int64(n) => Some(*n),
+        Bson::Double(n) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+/// The document holding `queryPlanner` / `executionStats`: the explain itself for finds
+/// and pushed-down pipelines, or the first stage's `$cursor` for classic aggregate explains
+fn plan_root(explain: &Document) -> &Document {
+    if explain.contains_key("queryPlanner") {
+        return explain;
+    }
+    explain.get_array("stages").ok()
+        .and_then(|stages| stages.first())
+        .and_then(|stage| stage.as_document())
+        .and_then(|stage| stage.get_document("$cursor").ok())
+        .unwrap_or(explain)
+}
+
+/// Condense a find or aggregate explain result into an `ExplainSummary`
+pub fn summarize_plan(explain: &Document) -> ExplainSummary {
+    let root = plan_root(explain);
+
+    let winning_plan = root
+        .get_document("queryPlanner")
+        .and_then(|planner| planner.get_document("winningPlan"))
+        .ok()
+        // Slot-based engine plans wrap the classic tree in `queryPlan`
+        .map(|plan| plan.get_document("queryPlan").unwrap_or(plan));
+
+    let mut stages = Vec::new();
+    let mut indexes = Vec::new();
+    if let Some(plan) = winning_plan {
+        collect_plan_stages(plan, &mut stages, &mut indexes);
+    }
+
+    let execution_stats = root.get_document("executionStats").ok();
+
+    ExplainSummary {
+        stage: stages.first().cloned(),
+        index: indexes.first().cloned(),
+        collection_scan: stages.iter().any(|s| s == "COLLSCAN"),
+        docs_examined: execution_stats.and_then(|stats| get_number(stats, "totalDocsExamined")),
+        keys_examined: execution_stats.and_then(|stats| get_number(stats, "totalKeysExamined")),
+        returned: execution_stats.and_then(|stats| get_number(stats, "nReturned")),
+        millis: execution_stats.and_then(|stats| get_number(stats, "executionTimeMillis")),
+        stages,
+        indexes,
+    }
+}
+
+fn collect_plan_stages(plan: &Document, stages: &mut Vec<String>, indexes: &mut Vec<String>) {
+    if let Ok(stage) = plan.get_str("stage") {
+        stages.push(stage.to_string());
+    }
+    if let Ok(index_name) = plan.get_str("indexName") {
+        indexes.push(index_name.to_string());
+    }
+    if let Ok(input) = plan.get_document("inputStage") {
+        collect_plan_stages(input, stages, indexes);
+    }
+    if let Ok(inputs) = plan.get_array("inputStages") {
+        for input in inputs.iter().filter_map(|i| i.as_document()) {
+            collect_plan_stages(input, stages, indexes);
+        }
+    }
+}
+
 pub async fn get_collection_stats(
     collection: Collection<Document>,
 ) -> mongodb::error::Result<Document> {
-    let db = collection.database();
+    let db = collection.client().database(&collection.namespace().db);
     let coll_name = collection.name();
     db.run_command(
         mongodb::bson::doc! {
@@ -54,3 +317,357 @@ pub async fn get_collection_stats(
     ).await
 }
 
+
+pub async fn get_plan_cache(
+    collection: Collection<Document>,
+) -> mongodb::error::Result<Vec<Document>> {
+    let mut cursor = collection
+        .aggregate(vec![mongodb::bson::doc! { "$planCacheStats": {} }], None)
+        .await?;
+
+    let mut entries = Vec::new();
+    while let Some(entry) = cursor.next().await {
+        entries.push(entry?);
+    }
+
+    Ok(entries)
+}
+
+pub async fn clear_plan_cache(
+    database: Database,
+    collection_name: String,
+) -> mongodb::error::Result<()> {
+    database.run_command(
+        mongodb::bson::doc! {
+            "planCacheClear": collection_name
+        },
+        None,
+    ).await?;
+
+    Ok(())
+}
+
+/// Group `system.profile` entries from the last `since_minutes` by operation type and by
+/// namespace, each sorted by total time spent. `level` is 0 when profiling is off, in
+/// which case the groups are simply empty.
+pub async fn profiler_summary(
+    database: Database,
+    since_minutes: u64,
+) -> mongodb::error::Result<Document> {
+    let status = database.run_command(mongodb::bson::doc! { "profile": -1 }, None).await?;
+    let level = status.get("was").and_then(Bson::as_i32).unwrap_or(0);
+
+    let since = mongodb::bson::DateTime::from_millis(
+        chrono::Utc::now().timestamp_millis() - (since_minutes as i64).saturating_mul(60_000)
+    );
+    let group_by = |key: &str| vec![
+        mongodb::bson::doc! { "$group": {
+            "_id": format!("${}", key),
+            "count": { "$sum": 1 },
+            "total_millis": { "$sum": "$millis" },
+            "avg_millis": { "$avg": "$millis" },
+            "max_millis": { "$max": "$millis" },
+        }},
+        mongodb::bson::doc! { "$sort": { "total_millis": -1 } },
+    ];
+
+    let mut cursor = database
+        .collection::<Document>("system.profile")
+        .aggregate(vec![
+            mongodb::bson::doc! { "$match": { "ts": { "$gte": since } } },
+            mongodb::bson::doc! { "$facet": {
+                "by_operation": group_by("op"),
+                "by_namespace": group_by("ns"),
+            }},
+        ], None)
+        .await?;
+
+    let facets = match cursor.next().await {
+        Some(result) => result?,
+        None => Document::new(),
+    };
+
+    Ok(mongodb::bson::doc! {
+        "level": level,
+        "since": since,
+        "by_operation": facets.get_array("by_operation").cloned().unwrap_or_default(),
+        "by_namespace": facets.get_array("by_namespace").cloned().unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explain(json: &str) -> Document {
+        serde_json::from_str(json).unwrap()
+    }
+
+    // A find explain as a 6.0 server with the classic engine returns it, trimmed
+    const FIND_EXPLAIN: &str = r#"{
+        "queryPlanner": {
+            "namespace": "shop.orders",
+            "winningPlan": {
+                "stage": "FETCH",
+                "inputStage": { "stage": "IXSCAN", "keyPattern": { "status": 1 }, "indexName": "status_1" }
+            },
+            "rejectedPlans": []
+        },
+        "executionStats": {
+            "executionSuccess": true,
+            "nReturned": 3,
+            "executionTimeMillis": 2,
+            "totalKeysExamined": 3,
+            "totalDocsExamined": 3
+        },
+        "ok": 1.0
+    }"#;
+
+    // The same filter run as `[{$match}, {$group}]`, where the `$match` is not pushed
+    // into the query layer and the plan sits under the first stage's `$cursor`
+    const AGGREGATE_EXPLAIN: &str = r#"{
+        "stages": [
+            {
+                "$cursor": {
+                    "queryPlanner": {
+                        "namespace": "shop.orders",
+                        "winningPlan": {
+                            "queryPlan": {
+                                "stage": "PROJECTION_COVERED",
+                                "inputStage": { "stage": "IXSCAN", "indexName": "status_1" }
+                            },
+                            "slotBasedPlan": { "slots": "..." }
+                        }
+                    },
+                    "executionStats": {
+                        "nReturned": 3,
+                        "executionTimeMillis": 4,
+                        "totalKeysExamined": 3,
+                        "totalDocsExamined": 0
+                    }
+                },
+                "nReturned": 3
+            },
+            { "$group": { "_id": "$status", "n": { "$sum": { "$const": 1 } } }, "nReturned": 1 }
+        ],
+        "ok": 1.0
+    }"#;
+
+    #[test]
+    fn summarizes_a_find_explain() {
+        let summary = summarize_plan(&explain(FIND_EXPLAIN));
+        assert_eq!(summary.stage.as_deref(), Some("FETCH"));
+        assert_eq!(summary.stages, vec!["FETCH", "IXSCAN"]);
+        assert_eq!(summary.index.as_deref(), Some("status_1"));
+        assert!(!summary.collection_scan);
+        assert_eq!((summary.docs_examined, summary.keys_examined, summary.returned, summary.millis), (Some(3), Some(3), Some(3), Some(2)));
+        assert_eq!(summary.coverage(), "index_scan");
+    }
+
+    #[test]
+    fn summarizes_an_aggregate_explain_in_the_same_shape() {
+        let summary = summarize_plan(&explain(AGGREGATE_EXPLAIN));
+        assert_eq!(summary.stage.as_deref(), Some("PROJECTION_COVERED"));
+        assert_eq!(summary.index.as_deref(), Some("status_1"));
+        assert_eq!((summary.docs_examined, summary.keys_examined, summary.returned, summary.millis), (Some(0), Some(3), Some(3), Some(4)));
+        assert_eq!(summary.coverage(), "covered");
+    }
+
+    #[test]
+    fn summarizes_a_collection_scan() {
+        let summary = summarize_plan(&explain(r#"{
+            "queryPlanner": { "winningPlan": { "stage": "COLLSCAN", "direction": "forward" } },
+            "executionStats": { "nReturned": 1, "totalDocsExamined": 1000, "totalKeysExamined": 0, "executionTimeMillis": 9 }
+        }"#));
+        assert!(summary.collection_scan);
+        assert_eq!(summary.index, None);
+        assert_eq!(summary.coverage(), "collection_scan");
+    }
+
+    fn key(filter: Document) -> String {
+        explain_cache_key("conn:shop.orders", "find", &Bson::Document(filter))
+    }
+
+    #[test]
+    fn query_shape_replaces_literals() {
+        let filter = Bson::Document(mongodb::bson::doc! { "age": { "$gt": 20 }, "tags": { "$in": ["a", "b"] }, "name": "$x" });
+        assert_eq!(
+            query_shape(&filter),
+            Bson::Document(mongodb::bson::doc! { "age": { "$gt": "?" }, "tags": { "$in": "?" }, "name": "$x" })
+        );
+    }
+
+    #[test]
+    fn explain_cache_hits_for_the_same_shape() {
+        let mut cache = ExplainCache::default();
+        let plan = mongodb::bson::doc! { "queryPlanner": { "winningPlan": { "stage": "COLLSCAN" } } };
+        cache.store(key(mongodb::bson::doc! { "age": { "$gt": 20 } }), plan.clone());
+        assert_eq!(cache.get(&key(mongodb::bson::doc! { "age": { "$gt": 30 } })), Some(plan));
+    }
+
+    #[test]
+    fn explain_cache_misses_for_distinct_shapes() {
+        let mut cache = ExplainCache::default();
+        cache.store(key(mongodb::bson::doc! { "age": { "$gt": 20 } }), Document::new());
+        assert_eq!(cache.get(&key(mongodb::bson::doc! { "age": { "$lt": 20 } })), None);
+        assert_eq!(cache.get(&key(mongodb::bson::doc! { "name": { "$gt": 20 } })), None);
+        assert_eq!(cache.get(&explain_cache_key("conn:shop.users", "find", &Bson::Document(mongodb::bson::doc! { "age": { "$gt": 20 } }))), None);
+    }
+
+    #[test]
+    fn explain_cache_evicts_the_least_recently_used_entry() {
+        let mut cache = ExplainCache::default();
+        for i in 0..EXPLAIN_CACHE_CAPACITY {
+            cache.store(format!("key{}", i), Document::new());
+        }
+        // Reading key0 makes key1 the least recently used
+        assert!(cache.get("key0").is_some());
+        cache.store("one more".to_string(), Document::new());
+
+        assert!(cache.get("key1").is_none());
+        assert!(cache.get("key0").is_some());
+        assert_eq!(cache.entries.len(), EXPLAIN_CACHE_CAPACITY);
+    }
+
+    #[test]
+    fn explain_cache_drops_expired_entries() {
+        let mut cache = ExplainCache::default();
+        cache.entries.insert("stale".to_string(), (Instant::now() - EXPLAIN_CACHE_TTL, Document::new()));
+        cache.order.push_back("stale".to_string());
+        assert!(cache.get("stale").is_none());
+        assert!(cache.entries.is_empty() && cache.order.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn plan_cache_lists_and_clears() {
+        let db = crate::mongo::test_support::scratch_database().await;
+        let coll = db.collection::<Document>("items");
+        coll.insert_many((0..100).map(|i| mongodb::bson::doc! { "a": i, "b": i % 10 }), None).await.unwrap();
+        for keys in [mongodb::bson::doc! { "a": 1 }, mongodb::bson::doc! { "b": 1 }] {
+            coll.create_index(mongodb::IndexModel::builder().keys(keys).build(), None).await.unwrap();
+        }
+
+        // Two candidate indexes make the planner cache its choice
+        let mut cursor = coll.find(mongodb::bson::doc! { "a": { "$gte": 50 }, "b": 3 }, None).await.unwrap();
+        while cursor.next().await.is_some() {}
+
+        assert!(!get_plan_cache(coll.clone()).await.unwrap().is_empty());
+        clear_plan_cache(db.clone(), "items".to_string()).await.unwrap();
+        assert!(get_plan_cache(coll).await.unwrap().is_empty());
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn aggregate_plan_explain_summarizes_without_executing() {
+        let db = crate::mongo::test_support::scratch_database().await;
+        let coll = db.collection::<Document>("orders");
+        coll.insert_many((0..20).map(|i| mongodb::bson::doc! { "status": if i % 2 == 0 { "open" } else { "closed" } }), None).await.unwrap();
+        coll.create_index(mongodb::IndexModel::builder().keys(mongodb::bson::doc! { "status": 1 }).build(), None).await.unwrap();
+
+        let pipeline = vec![
+            mongodb::bson::doc! { "$match": { "status": "open" } },
+            mongodb::bson::doc! { "$group": { "_id": null, "n": { "$sum": 1 } } },
+        ];
+        let summary = summarize_plan(&explain_aggregate_plan(coll, pipeline).await.unwrap());
+
+        assert!(summary.stage.is_some(), "{:?}", summary);
+        assert_eq!(summary.indexes, vec!["status_1"]);
+        // queryPlanner verbosity runs nothing, so there are no execution stats
+        assert_eq!(summary.returned, None);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB 4.4+ server at MONGODB_TEST_URI with setParameter rights"]
+    async fn spilling_pipelines_explain_with_allow_disk_use() {
+        let client = crate::mongo::test_support::test_client().await;
+        let admin = client.database("admin");
+        let parameter = "internalQueryMaxBlockingSortMemoryUsageBytes";
+        let original = admin.run_command(mongodb::bson::doc! { "getParameter": 1, parameter: 1 }, None).await.unwrap();
+        let original = original.get(parameter).cloned().unwrap();
+
+        // Shrink the blocking sort budget so a small collection has to spill
+        admin.run_command(mongodb::bson::doc! { "setParameter": 1, parameter: 64 * 1024 }, None).await.unwrap();
+        let db = crate::mongo::test_support::scratch_database().await;
+        let coll = db.collection::<Document>("items");
+        let padding = "x".repeat(1024);
+        coll.insert_many((0..500).map(|i| mongodb::bson::doc! { "n": i, "padding": &padding }), None).await.unwrap();
+
+        let pipeline = vec![mongodb::bson::doc! { "$sort": { "n": -1 } }];
+        let refused = explain_aggregate(coll.clone(), pipeline.clone(), Some(false)).await;
+        let spilled = explain_aggregate(coll, pipeline, Some(true)).await;
+
+        admin.run_command(mongodb::bson::doc! { "setParameter": 1, parameter: original }, None).await.unwrap();
+        db.drop(None).await.unwrap();
+        assert!(refused.is_err());
+        assert_eq!(spilled.unwrap().get_f64("ok"), Ok(1.0));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn profiler_summary_groups_by_operation_and_namespace() {
+        let db = crate::mongo::test_support::scratch_database().await;
+        let orders = db.collection::<Document>("orders");
+        let users = db.collection::<Document>("users");
+        orders.insert_one(mongodb::bson::doc! { "_id": 0 }, None).await.unwrap();
+
+        let disabled = profiler_summary(db.clone(), 5).await.unwrap();
+        assert_eq!(disabled.get_i32("level").unwrap(), 0);
+        assert!(disabled.get_array("by_operation").unwrap().is_empty());
+
+        db.run_command(mongodb::bson::doc! { "profile": 2 }, None).await.unwrap();
+        orders.insert_many((1..4).map(|i| mongodb::bson::doc! { "_id": i }), None).await.unwrap();
+        for _ in 0..3 {
+            orders.find_one(None, None).await.unwrap();
+        }
+        users.insert_one(mongodb::bson::doc! { "name": "a" }, None).await.unwrap();
+
+        let summary = profiler_summary(db.clone(), 5).await.unwrap();
+        db.run_command(mongodb::bson::doc! { "profile": 0 }, None).await.unwrap();
+        assert_eq!(summary.get_i32("level").unwrap(), 2);
+
+        let count_for = |facet: &str, key: &str| -> i32 {
+            summary.get_array(facet).unwrap().iter()
+                .filter_map(Bson::as_document)
+                .find(|group| group.get_str("_id") == Ok(key))
+                .and_then(|group| group.get_i32("count").ok())
+                .unwrap_or(0)
+        };
+        assert_eq!(count_for("by_operation", "query"), 3);
+        assert_eq!(count_for("by_operation", "insert"), 2);
+        assert_eq!(count_for("by_namespace", &format!("{}.users", db.name())), 1);
+        assert!(count_for("by_namespace", &format!("{}.orders", db.name())) >= 4);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn indexed_equality_counts_use_count_scan() {
+        let db = crate::mongo::test_support::scratch_database().await;
+        let coll = db.collection::<Document>("orders");
+        coll.insert_many((0..20).map(|i| mongodb::bson::doc! { "status": i % 4 }), None).await.unwrap();
+        coll.create_index(mongodb::IndexModel::builder().keys(mongodb::bson::doc! { "status": 1 }).build(), None).await.unwrap();
+
+        let summary = summarize_plan(&explain_count(coll, mongodb::bson::doc! { "status": 2 }).await.unwrap());
+        assert!(summary.stages.iter().any(|stage| stage == "COUNT_SCAN"), "{:?}", summary);
+        assert!(!summary.collection_scan);
+        assert_eq!(summary.indexes, vec!["status_1"]);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn coverage_classifies_plans() {
+        let plan = |winning: &str| summarize_plan(&explain(&format!(r#"{{ "queryPlanner": {{ "winningPlan": {} }} }}"#, winning)));
+        assert_eq!(plan(r#"{ "stage": "COLLSCAN" }"#).coverage(), "collection_scan");
+        assert_eq!(plan(r#"{ "stage": "FETCH", "inputStage": { "stage": "IXSCAN", "indexName": "a_1" } }"#).coverage(), "index_scan");
+        assert_eq!(plan(r#"{ "stage": "PROJECTION_COVERED", "inputStage": { "stage": "IXSCAN", "indexName": "a_1" } }"#).coverage(), "covered");
+        assert_eq!(plan(r#"{ "stage": "IDHACK" }"#).coverage(), "index_scan");
+        assert_eq!(plan(r#"{ "stage": "EOF" }"#).coverage(), "other");
+    }
+}
@@ -1,4 +1,5 @@
-use mongodb::{bson::Document, Collection, options::FindOptions};
+use futures::StreamExt;
+use mongodb::{bson::{doc, Bson, Document}, Client, Collection, options::{CountOptions, FindOptions, ReadConcern, ReadPreference, ReadPreferenceOptions, SelectionCriteria, TagSet}};
 
 pub async fn find(
     collection: Collection<Document>,
@@ -14,6 +15,8 @@ pub async fn find_with_options(
     limit: Option<u64>,
     skip: Option<u64>,
     projection: Option<Document>,
+    comment: Option<String>,
+    max_time_ms: Option<u64>,
 ) -> mongodb::error::Result<mongodb::Cursor<Document>> {
     let mut options = FindOptions::default();
     
@@ -32,6 +35,544 @@ pub async fn find_with_options(
     if let Some(projection_doc) = projection {
         options.projection = Some(projection_doc);
     }
+
+    // Shows up in currentOp and system.profile for correlating UI actions with server ops
+    options.comment = comment;
+    options.max_time = max_time_ms.map(std::time::Duration::from_millis);
     
     collection.find(filter, Some(options)).await
 }
+
+#[derive(Clone, Copy, PartialEq)]
+enum ProjectionKind {
+    Include,
+    Exclude,
+    Neutral,
+}
+
+fn classify_projection_value(value: &Bson, fields: &mut Vec<(String, ProjectionKind)>, path: &str) {
+    let kind = match value {
+        Bson::Boolean(b) => if *b { ProjectionKind::Include } else { ProjectionKind::Exclude },
+        Bson::Int32(n) => if *n != 0 { ProjectionKind::Include } else { ProjectionKind::Exclude },
+        Bson::Int64(n) => if *n != 0 { ProjectionKind::Include } else { ProjectionKind::Exclude },
+        Bson::Double(n) => if *n != 0.0 { ProjectionKind::Include } else { ProjectionKind::Exclude },
+        Bson::Document(inner) => match inner.keys().next() {
+            // Projection operators are allowed in either kind of projection
+            Some(key) if matches!(key.as_str(), "$slice" | "$elemMatch" | "$meta") => ProjectionKind::Neutral,
+            // Any other operator is an aggregation expression, i.e. a computed field
+            Some(key) if key.starts_with('$') => ProjectionKind::Include,
+            _ => {
+                for (key, nested) in inner {
+                    classify_projection_value(nested, fields, &format!("{}.{}", path, key));
+                }
+                return;
+            }
+        },
+        // Literals and "$field" paths are computed fields
+        _ => ProjectionKind::Include,
+    };
+    fields.push((path.to_string(), kind));
+}
+
+/// Reject projections that mix inclusion (including computed fields) and exclusion,
+/// which the server refuses; `_id` is the only field that may be excluded alongside inclusions
+pub fn validate_projection(projection: &Document) -> Result<(), String> {
+    let mut fields = Vec::new();
+    for (key, value) in projection {
+        if key == "_id" {
+            continue;
+        }
+        classify_projection_value(value, &mut fields, key);
+    }
+
+    let included = fields.iter().find(|(_, kind)| *kind == ProjectionKind::Include);
+    let excluded = fields.iter().find(|(_, kind)| *kind == ProjectionKind::Exclude);
+    if let (Some((included, _)), Some((excluded, _))) = (included, excluded) {
+        return Err(format!(
+            "Projection cannot mix inclusion and exclusion: '{}' is included but '{}' is excluded. Only _id may be excluded in an inclusion projection",
+            included, excluded
+        ));
+    }
+    Ok(())
+}
+
+pub fn parse_read_concern(level: &str) -> Result<ReadConcern, String> {
+    match level {
+        "local" => Ok(ReadConcern::local()),
+        "available" => Ok(ReadConcern::available()),
+        "majority" => Ok(ReadConcern::majority()),
+        "linearizable" => Ok(ReadConcern::linearizable()),
+        "snapshot" => Ok(ReadConcern::snapshot()),
+        _ => Err(format!(
+            "Invalid read concern '{}'. Use 'local', 'available', 'majority', 'linearizable' or 'snapshot'",
+            level
+        )),
+    }
+}
+
+/// Parse read preference tag sets: a single object such as `{"region": "us-east"}`
+/// or an array of objects tried in order (`{}` matches any member)
+pub fn parse_tag_sets(value: &serde_json::Value) -> Result<Vec<TagSet>, String> {
+    let sets = match value {
+        serde_json::Value::Array(sets) => sets.clone(),
+        serde_json::Value::Object(_) => vec![value.clone()],
+        _ => return Err("Read preference tags must be an object or an array of objects".to_string()),
+    };
+
+    sets.iter()
+        .map(|set| {
+            let object = set.as_object()
+                .ok_or("Each read preference tag set must be an object")?;
+            object.iter()
+                .map(|(key, tag)| match tag.as_str() {
+                    Some(tag) => Ok((key.clone(), tag.to_string())),
+                    None => Err(format!("Read preference tag '{}' must be a string", key)),
+                })
+                .collect::<Result<TagSet, String>>()
+        })
+        .collect()
+}
+
+pub fn parse_read_preference(mode: &str, tag_sets: Option<Vec<TagSet>>) -> Result<SelectionCriteria, String> {
+    if mode == "primary" && tag_sets.as_ref().is_some_and(|sets| !sets.is_empty()) {
+        return Err("Read preference tags cannot be used with 'primary'".to_string());
+    }
+
+    let mut options = ReadPreferenceOptions::default();
+    options.tag_sets = tag_sets;
+    let read_preference = match mode {
+        "primary" => ReadPreference::Primary,
+        "primaryPreferred" => ReadPreference::PrimaryPreferred { options },
+        "secondary" => ReadPreference::Secondary { options },
+        "secondaryPreferred" => ReadPreference::SecondaryPreferred { options },
+        "nearest" => ReadPreference::Nearest { options },
+        _ => return Err(format!(
+            "Invalid read preference '{}'. Use 'primary', 'primaryPreferred', 'secondary', 'secondaryPreferred' or 'nearest'",
+            mode
+        )),
+    };
+    Ok(SelectionCriteria::ReadPreference(read_preference))
+}
+
+pub async fn count_documents(
+    collection: Collection<Document>,
+    filter: Document,
+    read_concern: Option<ReadConcern>,
+    selection_criteria: Option<SelectionCriteria>,
+) -> mongodb::error::Result<u64> {
+    let mut options = CountOptions::default();
+    options.read_concern = read_concern;
+    options.selection_criteria = selection_criteria;
+
+    collection.count_documents(filter, Some(options)).await
+}
+
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+/// Append `_id` as a final sort key so documents with equal sort values keep a stable order
+pub fn with_id_tiebreaker(sort: Option<Document>) -> Document {
+    let mut sort = sort.unwrap_or_default();
+    if !sort.contains_key("_id") {
+        sort.insert("_id", 1);
+    }
+    sort
+}
+
+/// One page of a find (0-based `page`) together with the total number of matches
+pub async fn find_page(
+    collection: Collection<Document>,
+    filter: Document,
+    sort: Option<Document>,
+    page: u64,
+    page_size: u64,
+) -> mongodb::error::Result<(Vec<Document>, u64)> {
+    let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+    let total_count = collection.count_documents(filter.clone(), None).await?;
+
+    let mut options = FindOptions::default();
+    options.sort = Some(with_id_tiebreaker(sort));
+    options.skip = Some(page.saturating_mul(page_size));
+    options.limit = Some(page_size as i64);
+
+    let mut cursor = collection.find(filter, Some(options)).await?;
+    let mut documents = Vec::with_capacity(page_size as usize);
+    while let Some(result) = cursor.next().await {
+        documents.push(result?);
+    }
+
+    Ok((documents, total_count))
+}
+
+/// Keyset pagination: the page of documents following `after`, a `(sort value, _id)`
+/// pair taken from the last document of the previous page. `_id` breaks ties so
+/// non-unique sort fields neither skip nor repeat documents.
+pub async fn find_after(
+    collection: Collection<Document>,
+    filter: Document,
+    sort_field: &str,
+    after: Option<(Bson, Bson)>,
+    page_size: u64,
+    descending: bool,
+) -> mongodb::error::Result<Vec<Document>> {
+    let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+    let direction = if descending { -1 } else { 1 };
+    let filter = keyset_filter(filter, sort_field, after, descending);
+
+    let mut sort = doc! { sort_field: direction };
+    if sort_field != "_id" {
+        sort.insert("_id", direction);
+    }
+
+    let mut options = FindOptions::default();
+    options.sort = Some(sort);
+    options.limit = Some(page_size as i64);
+
+    let mut cursor = collection.find(filter, Some(options)).await?;
+    let mut documents = Vec::with_capacity(page_size as usize);
+    while let Some(result) = cursor.next().await {
+        documents.push(result?);
+    }
+
+    Ok(documents)
+}
+
+/// The filter selecting documents that sort after `after` in `find_after`'s order.
+///
+/// Null and missing sort values sort before everything else, and `$gt` / `$lt` only
+/// compare values of the same type, so a null cursor gets its own bracket and values
+/// of other types are compared with an aggregation expression, which follows the
+/// server's cross-type sort order.
+pub fn keyset_filter(filter: Document, sort_field: &str, after: Option<(Bson, Bson)>, descending: bool) -> Document {
+    let operator = if descending { "$lt" } else { "$gt" };
+
+    let after = match after {
+        Some((_, last_id)) if sort_field == "_id" => doc! { "_id": { operator: last_id } },
+        Some((Bson::Null | Bson::Undefined, last_id)) if descending => doc! {
+            sort_field: Bson::Null, "_id": { operator: last_id }
+        },
+        Some((Bson::Null | Bson::Undefined, last_id)) => doc! { "$or": [
+            { sort_field: Bson::Null, "_id": { operator: last_id } },
+            { sort_field: { "$ne": Bson::Null } },
+        ] },
+        Some((last_value, last_id)) => {
+            let field_path = format!("${}", sort_field);
+            doc! { "$or": [
+                { sort_field: { operator: last_value.clone() } },
+                { sort_field: last_value.clone(), "_id": { operator: last_id } },
+                {
+                    sort_field: { "$not": { "$type": type_bracket(&last_value) } },
+                    "$expr": { operator: [field_path, { "$literal": last_value }] },
+                },
+            ] }
+        }
+        None => return filter,
+    };
+
+    if filter.is_empty() {
+        after
+    } else {
+        doc! { "$and": [filter, after] }
+    }
+}
+
+/// The `$type` alias covering every value `$gt` / `$lt` compare `value` against
+fn type_bracket(value: &Bson) -> Bson {
+    match value {
+        Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_) => Bson::String("number".to_string()),
+        other => Bson::Int32(other.element_type() as i32),
+    }
+}
+
+/// Open a tailable-await cursor on a capped collection, starting `last_n` documents
+/// before the end (or at the beginning when `None`). When re-opening a tail, pass the
+/// `_id` of the last document seen as `after` to resume past it instead.
+pub async fn tail_capped(
+    collection: Collection<Document>,
+    last_n: Option<u64>,
+    after: Option<Bson>,
+) -> mongodb::error::Result<mongodb::Cursor<Document>> {
+    let mut options = FindOptions::default();
+    options.cursor_type = Some(mongodb::options::CursorType::TailableAwait);
+    options.max_await_time = Some(std::time::Duration::from_secs(1));
+
+    let filter = match after {
+        Some(last_id) => Some(doc! { "_id": { "$gt": last_id } }),
+        None => {
+            if let Some(last_n) = last_n {
+                let total = collection.estimated_document_count(None).await?;
+                options.skip = Some(total.saturating_sub(last_n));
+            }
+            None
+        }
+    };
+
+    collection.find(filter, Some(options)).await
+}
+
+/// On sharded clusters older than 4.4, orphaned documents left behind by chunk
+/// migrations can be included in counts. Returns a note when that may apply.
+pub async fn orphaned_documents_note(client: &Client) -> Option<String> {
+    let admin = client.database("admin");
+    let hello = admin.run_command(doc! { "hello": 1 }, None).await.ok()?;
+    if hello.get_str("msg").ok()? != "isdbgrid" {
+        return None;
+    }
+
+    let build_info = admin.run_command(doc! { "buildInfo": 1 }, None).await.ok()?;
+    let version = build_info.get_str("version").ok()?.to_string();
+    let mut parts = version.split('.').map(|p| p.parse::<u32>().unwrap_or(0));
+    let (major, minor) = (parts.next().unwrap_or(0), parts.next().unwrap_or(0));
+
+    if (major, minor) < (4, 4) {
+        Some(format!(
+            "Connected to a sharded cluster running MongoDB {}: orphaned documents from chunk migrations may inflate this count",
+            version
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::test_support::scratch_database;
+
+    #[test]
+    fn keyset_filter_passes_the_first_page_through() {
+        let filter = doc! { "status": "active" };
+        assert_eq!(keyset_filter(filter.clone(), "age", None, false), filter);
+    }
+
+    #[test]
+    fn keyset_filter_on_id_uses_a_single_range() {
+        let after = Some((Bson::Int32(7), Bson::Int32(7)));
+        assert_eq!(keyset_filter(Document::new(), "_id", after, true), doc! { "_id": { "$lt": 7 } });
+    }
+
+    #[test]
+    fn keyset_filter_after_null_moves_on_to_non_null_values() {
+        let after = Some((Bson::Null, Bson::Int32(3)));
+        assert_eq!(
+            keyset_filter(doc! { "status": "active" }, "age", after, false),
+            doc! { "$and": [
+                { "status": "active" },
+                { "$or": [
+                    { "age": Bson::Null, "_id": { "$gt": 3 } },
+                    { "age": { "$ne": Bson::Null } },
+                ] },
+            ] }
+        );
+    }
+
+    #[test]
+    fn keyset_filter_after_null_descending_stays_in_the_null_bracket() {
+        let after = Some((Bson::Null, Bson::Int32(3)));
+        assert_eq!(
+            keyset_filter(Document::new(), "age", after, true),
+            doc! { "age": Bson::Null, "_id": { "$lt": 3 } }
+        );
+    }
+
+    #[test]
+    fn keyset_filter_compares_other_types_in_sort_order() {
+        let after = Some((Bson::Int64(30), Bson::Int32(3)));
+        assert_eq!(
+            keyset_filter(Document::new(), "age", after, false),
+            doc! { "$or": [
+                { "age": { "$gt": 30_i64 } },
+                { "age": 30_i64, "_id": { "$gt": 3 } },
+                {
+                    "age": { "$not": { "$type": "number" } },
+                    "$expr": { "$gt": ["$age", { "$literal": 30_i64 }] },
+                },
+            ] }
+        );
+    }
+
+    #[test]
+    fn keyset_filter_brackets_non_numeric_values_by_type() {
+        let after = Some((Bson::String("$x".to_string()), Bson::Int32(3)));
+        let filter = keyset_filter(Document::new(), "name", after, true);
+        let branches = filter.get_array("$or").unwrap();
+        assert_eq!(
+            branches[2],
+            Bson::Document(doc! {
+                "name": { "$not": { "$type": 2 } },
+                "$expr": { "$lt": ["$name", { "$literal": "$x" }] },
+            })
+        );
+    }
+
+    #[test]
+    fn validate_projection_accepts_computed_fields() {
+        let projection = doc! {
+            "_id": 0,
+            "fullName": { "$concat": ["$first", " ", "$last"] },
+            "email": 1,
+            "comments": { "$slice": 3 },
+            "source": "import",
+        };
+        assert!(validate_projection(&projection).is_ok());
+        assert!(validate_projection(&doc! { "password": 0, "address.zip": false, "scores": { "$elemMatch": { "$gt": 5 } } }).is_ok());
+    }
+
+    #[test]
+    fn validate_projection_rejects_mixing_inclusion_and_exclusion() {
+        let error = validate_projection(&doc! { "fullName": { "$concat": ["$first", "$last"] }, "password": 0 }).unwrap_err();
+        assert!(error.contains("'fullName' is included but 'password' is excluded"), "{}", error);
+        assert!(validate_projection(&doc! { "address": { "city": 1, "zip": 0 } }).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn find_applies_computed_projections() {
+        let db = scratch_database().await;
+        let people = db.collection::<Document>("people");
+        people.insert_one(doc! { "_id": 1, "first": "Ada", "last": "Lovelace" }, None).await.unwrap();
+
+        let projection = doc! { "_id": 0, "fullName": { "$concat": ["$first", " ", "$last"] } };
+        validate_projection(&projection).unwrap();
+        let mut cursor = find_with_options(people, Document::new(), None, None, None, Some(projection), None, None).await.unwrap();
+        assert_eq!(cursor.next().await.unwrap().unwrap(), doc! { "fullName": "Ada Lovelace" });
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn parse_tag_sets_accepts_an_object_or_an_array() {
+        let single = parse_tag_sets(&serde_json::json!({ "region": "us-east", "use": "analytics" })).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].get("region").map(String::as_str), Some("us-east"));
+        assert_eq!(single[0].get("use").map(String::as_str), Some("analytics"));
+
+        let ordered = parse_tag_sets(&serde_json::json!([{ "region": "us-east" }, {}])).unwrap();
+        assert_eq!(ordered.len(), 2);
+        assert!(ordered[1].is_empty());
+    }
+
+    #[test]
+    fn parse_tag_sets_rejects_malformed_tags() {
+        assert!(parse_tag_sets(&serde_json::json!("us-east")).is_err());
+        assert!(parse_tag_sets(&serde_json::json!([["region"]])).is_err());
+        let error = parse_tag_sets(&serde_json::json!({ "rack": 3 })).unwrap_err();
+        assert_eq!(error, "Read preference tag 'rack' must be a string");
+    }
+
+    #[test]
+    fn tags_are_refused_with_primary() {
+        let tags = parse_tag_sets(&serde_json::json!({ "region": "us-east" })).unwrap();
+        assert!(parse_read_preference("primary", Some(tags.clone())).is_err());
+        let criteria = parse_read_preference("secondary", Some(tags.clone())).unwrap();
+        assert!(matches!(
+            criteria,
+            SelectionCriteria::ReadPreference(ReadPreference::Secondary { options }) if options.tag_sets == Some(tags)
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a replica set at MONGODB_TEST_URI with a secondary tagged {region: \"us-east\"}"]
+    async fn tagged_reads_reach_a_tagged_member() {
+        let tags = parse_tag_sets(&serde_json::json!({ "region": "us-east" })).unwrap();
+        let criteria = parse_read_preference("secondary", Some(tags)).unwrap();
+        let client = crate::mongo::test_support::test_client().await;
+
+        // `hello` reports the tags of the member that answered it
+        let hello = client.database("admin").run_command(doc! { "hello": 1 }, Some(criteria)).await.unwrap();
+        assert_eq!(hello.get_document("tags").unwrap().get_str("region"), Ok("us-east"));
+        assert_eq!(hello.get_bool("secondary"), Ok(true));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn tail_reopens_past_the_last_document() {
+        let db = scratch_database().await;
+        let options = mongodb::options::CreateCollectionOptions::builder().capped(true).size(4096).build();
+        db.create_collection("log", options).await.unwrap();
+        let log = db.collection::<Document>("log");
+
+        // An empty capped collection gives a cursor the server has already closed
+        let mut cursor = tail_capped(log.clone(), None, None).await.unwrap();
+        assert!(cursor.next().await.is_none());
+
+        log.insert_many(vec![doc! { "_id": 1 }, doc! { "_id": 2 }], None).await.unwrap();
+        let mut cursor = tail_capped(log.clone(), None, Some(Bson::Int32(1))).await.unwrap();
+        assert_eq!(cursor.next().await.unwrap().unwrap(), doc! { "_id": 2 });
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn parse_read_concern_rejects_unknown_levels() {
+        assert_eq!(parse_read_concern("majority").unwrap(), ReadConcern::majority());
+        assert!(parse_read_concern("strong").unwrap_err().contains("'majority'"));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn majority_counts_are_exact() {
+        let db = scratch_database().await;
+        let coll = db.collection::<Document>("items");
+        coll.insert_many((0..5).map(|i| doc! { "_id": i, "even": i % 2 == 0 }), None).await.unwrap();
+
+        let majority = Some(parse_read_concern("majority").unwrap());
+        assert_eq!(count_documents(coll.clone(), Document::new(), majority.clone(), None).await.unwrap(), 5);
+        assert_eq!(count_documents(coll, doc! { "even": true }, majority, None).await.unwrap(), 3);
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn comments_reach_the_profiler() {
+        let db = scratch_database().await;
+        let coll = db.collection::<Document>("items");
+        coll.insert_one(doc! { "_id": 1 }, None).await.unwrap();
+        db.run_command(doc! { "profile": 2 }, None).await.unwrap();
+
+        let mut cursor = find_with_options(coll.clone(), Document::new(), None, None, None, None, Some("ui:find".into()), None).await.unwrap();
+        while cursor.next().await.is_some() {}
+        let mut cursor = crate::mongo::aggregation::aggregate(coll, vec![doc! { "$match": {} }], Some("ui:aggregate".into()), None).await.unwrap();
+        while cursor.next().await.is_some() {}
+
+        db.run_command(doc! { "profile": 0 }, None).await.unwrap();
+        let profile = db.collection::<Document>("system.profile");
+        for (op, comment) in [("query", "ui:find"), ("command", "ui:aggregate")] {
+            let entry = profile.find_one(doc! { "op": op, "command.comment": comment }, None).await.unwrap();
+            assert!(entry.is_some(), "no {} profiler entry with comment {}", op, comment);
+        }
+
+        db.drop(None).await.unwrap();
+    }
+
+    #[test]
+    fn id_tiebreaker_is_appended_once() {
+        assert_eq!(with_id_tiebreaker(None), doc! { "_id": 1 });
+        assert_eq!(with_id_tiebreaker(Some(doc! { "score": -1 })), doc! { "score": -1, "_id": 1 });
+        assert_eq!(with_id_tiebreaker(Some(doc! { "_id": -1 })), doc! { "_id": -1 });
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn pages_cover_every_document_once() {
+        let db = scratch_database().await;
+        let coll = db.collection::<Document>("items");
+        // Only three distinct sort values, so page boundaries fall inside ties
+        coll.insert_many((0..25).map(|i| doc! { "_id": i, "bucket": i % 3 }), None).await.unwrap();
+
+        let mut seen = Vec::new();
+        for page in 0..3 {
+            let (documents, total) = find_page(coll.clone(), Document::new(), Some(doc! { "bucket": 1 }), page, 10).await.unwrap();
+            assert_eq!(total, 25);
+            assert_eq!(documents.len(), if page < 2 { 10 } else { 5 });
+            seen.extend(documents.iter().map(|d| d.get_i32("_id").unwrap()));
+        }
+        let mut sorted = seen.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted, (0..25).collect::<Vec<_>>());
+        assert_eq!(seen.len(), 25);
+
+        db.drop(None).await.unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+use mongodb::{Collection, bson::{Bson, Document, doc}};
+use futures::StreamExt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+const DEFAULT_SAMPLE_SIZE: usize = 1000;
+const MAX_EXAMPLE_IDS: usize = 5;
+
+/// Randomly sample documents from a collection using `$sample`
+pub async fn sample_documents(
+    collection: Collection<Document>,
+    sample_size: Option<usize>,
+) -> mongodb::error::Result<Vec<Document>> {
+    let size = sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE).max(1);
+    let mut cursor = collection
+        .aggregate(vec![doc! { "$sample": { "size": size as i64 } }], None)
+        .await?;
+
+    let mut documents = Vec::with_capacity(size);
+    while let Some(result) = cursor.next().await {
+        documents.push(result?);
+    }
+
+    Ok(documents)
+}
+
+/// Resolve a dotted path (e.g. "address.city") inside a document
+pub fn get_path<'a>(doc: &'a Document, path: &str) -> Option<&'a Bson> {
+    let mut parts = path.split('.');
+    let mut current = doc.get(parts.next()?)?;
+    for part in parts {
+        current = match current {
+            Bson::Document(inner) => inner.get(part)?,
+            Bson::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// BSON type name as reported by the server's `$type` operator
+pub fn bson_type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Document(_) => "object",
+        Bson::Array(_) => "array",
+        Bson::Binary(_) => "binData",
+        Bson::Undefined => "undefined",
+        Bson::ObjectId(_) => "objectId",
+        Bson::Boolean(_) => "bool",
+        Bson::DateTime(_) => "date",
+        Bson::Null => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::DbPointer(_) => "dbPointer",
+        Bson::JavaScriptCode(_) => "javascript",
+        Bson::Symbol(_) => "symbol",
+        Bson::JavaScriptCodeWithScope(_) => "javascriptWithScope",
+        Bson::Int32(_) => "int",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Int64(_) => "long",
+        Bson::Decimal128(_) => "decimal",
+        Bson::MinKey => "minKey",
+        Bson::MaxKey => "maxKey",
+    }
+}
+
+/// Report documents whose `field` type deviates from the most common type in the sample
+pub async fn detect_type_mismatches(
+    collection: Collection<Document>,
+    field: &str,
+    sample_size: Option<usize>,
+) -> mongodb::error::Result<Document> {
+    let documents = sample_documents(collection, sample_size).await?;
+    Ok(type_mismatch_report(&documents, field))
+}
+
+fn type_mismatch_report(documents: &[Document], field: &str) -> Document {
+    let mut type_counts: HashMap<&'static str, i64> = HashMap::new();
+    let mut type_examples: HashMap<&'static str, Vec<Bson>> = HashMap::new();
+    let mut missing = 0i64;
+
+    for document in documents {
+        match get_path(document, field) {
+            Some(value) => {
+                let type_name = bson_type_name(value);
+                *type_counts.entry(type_name).or_insert(0) += 1;
+                let examples = type_examples.entry(type_name).or_default();
+                if examples.len() < MAX_EXAMPLE_IDS {
+                    examples.push(document.get("_id").cloned().unwrap_or(Bson::Null));
+                }
+            }
+            None => missing += 1,
+        }
+    }
+
+    // Ties are broken by type name so the result is deterministic
+    let dominant_type = type_counts
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(type_name, _)| *type_name);
+
+    let mut mismatches: Vec<Document> = type_counts
+        .iter()
+        .filter(|(type_name, _)| Some(**type_name) != dominant_type)
+        .map(|(type_name, count)| doc! {
+            "type": *type_name,
+            "count": *count,
+            "example_ids": type_examples.remove(type_name).unwrap_or_default(),
+        })
+        .collect();
+    mismatches.sort_by_key(|d| std::cmp::Reverse(d.get_i64("count").unwrap_or(0)));
+
+    let mut counts = Document::new();
+    for (type_name, count) in &type_counts {
+        counts.insert(*type_name, *count);
+    }
+
+    doc! {
+        "field": field,
+        "sampled": documents.len() as i64,
+        "missing": missing,
+        "dominant_type": dominant_type.map(Bson::from).unwrap_or(Bson::Null),
+        "type_counts": counts,
+        "mismatches": mismatches,
+    }
+}
+
+#[derive(Default)]
+struct FieldStats {
+    count: usize,
+    object_count: usize,
+    types: BTreeSet<&'static str>,
+    children: BTreeMap<String, FieldStats>,
+}
+
+fn collect_field_stats(document: &Document, fields: &mut BTreeMap<String, FieldStats>) {
+    for (key, value) in document {
+        let stats = fields.entry(key.clone()).or_default();
+        stats.count += 1;
+        stats.types.insert(bson_type_name(value));
+        if let Bson::Document(inner) = value {
+            stats.object_count += 1;
+            collect_field_stats(inner, &mut stats.children);
+        }
+    }
+}
+
+fn object_schema(fields: &BTreeMap<String, FieldStats>, total: usize, required_threshold: f64) -> Document {
+    let mut properties = Document::new();
+    let mut required = Vec::new();
+
+    for (name, stats) in fields {
+        if total > 0 && stats.count as f64 / total as f64 >= required_threshold {
+            required.push(Bson::String(name.clone()));
+        }
+
+        let mut property = if stats.children.is_empty() {
+            Document::new()
+        } else {
+            object_schema(&stats.children, stats.object_count, required_threshold)
+        };
+        let types: Vec<&str> = stats.types.iter().copied().collect();
+        if types.len() == 1 {
+            property.insert("bsonType", types[0]);
+        } else {
+            property.insert("bsonType", types);
+        }
+        properties.insert(name.clone(), property);
+    }
+
+    let mut schema = doc! { "bsonType": "object" };
+    if !required.is_empty() {
+        schema.insert("required", required);
+    }
+    schema.insert("properties", properties);
+    schema
+}
+
+/// Build a `$jsonSchema` validator from sampled documents. A field is marked
+/// required when it appears in at least `required_threshold` (0.0-1.0) of the
+/// documents that contain its parent object.
+pub async fn generate_json_schema(
+    collection: Collection<Document>,
+    sample_size: Option<usize>,
+    required_threshold: f64,
+) -> mongodb::error::Result<Document> {
+    let documents = sample_documents(collection, sample_size).await?;
+    Ok(json_schema_for(&documents, required_threshold))
+}
+
+fn json_schema_for(documents: &[Document], required_threshold: f64) -> Document {
+    let mut fields = BTreeMap::new();
+    for document in documents {
+        collect_field_stats(document, &mut fields);
+    }
+
+    doc! {
+        "$jsonSchema": object_schema(&fields, documents.len(), required_threshold)
+    }
+}
+
+/// Flatten nested field stats into dotted paths and their observed types
+fn flatten_field_types(
+    fields: &BTreeMap<String, FieldStats>,
+    prefix: &str,
+    paths: &mut BTreeMap<String, BTreeSet<&'static str>>,
+) {
+    for (name, stats) in fields {
+        let path = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+        paths.insert(path.clone(), stats.types.clone());
+        flatten_field_types(&stats.children, &path, paths);
+    }
+}
+
+fn field_types(documents: &[Document]) -> BTreeMap<String, BTreeSet<&'static str>> {
+    let mut fields = BTreeMap::new();
+    for document in documents {
+        collect_field_stats(document, &mut fields);
+    }
+
+    let mut paths = BTreeMap::new();
+    flatten_field_types(&fields, "", &mut paths);
+    paths
+}
+
+/// Compare the sampled schemas of two collections: fields only in A, only in B,
+/// and fields present in both whose observed type sets differ
+pub async fn diff_schemas(
+    collection_a: Collection<Document>,
+    collection_b: Collection<Document>,
+    sample_size: Option<usize>,
+) -> mongodb::error::Result<Document> {
+    let (sample_a, sample_b) = tokio::join!(
+        sample_documents(collection_a, sample_size),
+        sample_documents(collection_b, sample_size),
+    );
+    Ok(diff_field_types(&field_types(&sample_a?), &field_types(&sample_b?)))
+}
+
+fn diff_field_types(
+    fields_a: &BTreeMap<String, BTreeSet<&'static str>>,
+    fields_b: &BTreeMap<String, BTreeSet<&'static str>>,
+) -> Document {
+    let only_in_a: Vec<&String> = fields_a.keys().filter(|path| !fields_b.contains_key(*path)).collect();
+    let only_in_b: Vec<&String> = fields_b.keys().filter(|path| !fields_a.contains_key(*path)).collect();
+    let type_changes: Vec<Document> = fields_a
+        .iter()
+        .filter_map(|(path, types_a)| {
+            let types_b = fields_b.get(path)?;
+            (types_a != types_b).then(|| doc! {
+                "field": path,
+                "types_a": types_a.iter().copied().collect::<Vec<_>>(),
+                "types_b": types_b.iter().copied().collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    doc! {
+        "only_in_a": only_in_a,
+        "only_in_b": only_in_b,
+        "type_changes": type_changes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_mismatches_report_each_deviant_type_with_examples() {
+        let documents = vec![
+            doc! { "_id": 1, "price": 10 },
+            doc! { "_id": 2, "price": "12.50" },
+            doc! { "_id": 3, "price": 7 },
+            doc! { "_id": 4, "price": 3 },
+            doc! { "_id": 5, "price": "free" },
+            doc! { "_id": 6, "price": 2.5 },
+            doc! { "_id": 7 },
+        ];
+        let report = type_mismatch_report(&documents, "price");
+
+        assert_eq!(report.get_str("dominant_type"), Ok("int"));
+        assert_eq!(report.get_i64("sampled"), Ok(7));
+        assert_eq!(report.get_i64("missing"), Ok(1));
+        assert_eq!(report.get_document("type_counts").unwrap().get_i64("int"), Ok(3));
+        assert_eq!(report.get_array("mismatches").unwrap(), &vec![
+            Bson::Document(doc! { "type": "string", "count": 2_i64, "example_ids": [2, 5] }),
+            Bson::Document(doc! { "type": "double", "count": 1_i64, "example_ids": [6] }),
+        ]);
+    }
+
+    #[test]
+    fn json_schema_marks_common_fields_required() {
+        let documents = vec![
+            doc! { "_id": 1, "name": "Ada", "address": { "city": "London", "zip": "N1" } },
+            doc! { "_id": 2, "name": "Alan", "address": { "city": "Wilmslow" }, "nickname": "Prof" },
+            doc! { "_id": 3, "name": "Grace", "address": { "city": "Arlington", "zip": "22201" } },
+        ];
+        assert_eq!(json_schema_for(&documents, 1.0), doc! { "$jsonSchema": {
+            "bsonType": "object",
+            "required": ["_id", "address", "name"],
+            "properties": {
+                "_id": { "bsonType": "int" },
+                "address": {
+                    "bsonType": "object",
+                    "required": ["city"],
+                    "properties": {
+                        "city": { "bsonType": "string" },
+                        "zip": { "bsonType": "string" },
+                    },
+                },
+                "name": { "bsonType": "string" },
+                "nickname": { "bsonType": "string" },
+            },
+        } });
+    }
+
+    #[test]
+    fn json_schema_threshold_relaxes_required() {
+        let documents = vec![
+            doc! { "a": 1, "b": 1 },
+            doc! { "a": 2, "b": "x" },
+            doc! { "a": 3 },
+        ];
+        let schema = json_schema_for(&documents, 0.6);
+        let schema = schema.get_document("$jsonSchema").unwrap();
+        assert_eq!(schema.get_array("required").unwrap(), &vec![Bson::from("a"), Bson::from("b")]);
+        assert_eq!(
+            schema.get_document("properties").unwrap().get_document("b").unwrap(),
+            &doc! { "bsonType": ["int", "string"] }
+        );
+    }
+
+    #[test]
+    fn schema_diff_reports_extra_fields_and_type_changes() {
+        let staging = vec![
+            doc! { "_id": 1, "price": 10, "address": { "city": "Oslo" } },
+            doc! { "_id": 2, "price": 12, "legacy": true },
+        ];
+        let production = vec![
+            doc! { "_id": 1, "price": "10", "address": { "city": "Oslo", "zip": "0150" } },
+            doc! { "_id": 2, "price": 12.5 },
+        ];
+
+        let diff = diff_field_types(&field_types(&staging), &field_types(&production));
+        assert_eq!(diff, doc! {
+            "only_in_a": ["legacy"],
+            "only_in_b": ["address.zip"],
+            "type_changes": [{ "field": "price", "types_a": ["int"], "types_b": ["double", "string"] }],
+        });
+    }
+}
@@ -0,0 +1,20 @@
+//! Helpers shared by the tests that need a MongoDB server. Those tests are
+//! `#[ignore]`d; run them with `cargo test -- --ignored`.
+
+use mongodb::{Client, Database};
+use uuid::Uuid;
+
+/// Server for the ignored tests, from `MONGODB_TEST_URI`
+pub fn test_uri() -> String {
+    std::env::var("MONGODB_TEST_URI").unwrap_or_else(|_| "mongodb://localhost:27017".to_string())
+}
+
+pub async fn test_client() -> Client {
+    Client::with_uri_str(test_uri()).await.expect("Failed to create test client")
+}
+
+/// A database with a unique name, so concurrent tests never share collections.
+/// Drop it at the end of the test.
+pub async fn scratch_database() -> Database {
+    test_client().await.database(&format!("novadb_test_{}", Uuid::new_v4().simple()))
+}
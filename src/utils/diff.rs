@@ -0,0 +1,56 @@
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::utils::matcher;
+
+/// Compare two result sets matched up by `key_field` (a dotted path, usually `_id`).
+/// Documents without the key are ignored.
+pub fn diff_documents(before: &[Value], after: &[Value], key_field: &str) -> Value {
+    let index = |docs: &[Value]| -> BTreeMap<String, Value> {
+        docs.iter()
+            .filter_map(|doc| matcher::get_path(doc, key_field).map(|key| (key.to_string(), doc.clone())))
+            .collect()
+    };
+    let before = index(before);
+    let mut after = index(after);
+
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+
+    for (key, old) in before {
+        match after.remove(&key) {
+            Some(new) if new == old => unchanged += 1,
+            Some(new) => changed.push(json!({
+                "key": matcher::get_path(&new, key_field).cloned(),
+                "fields": changed_fields(&old, &new),
+                "before": old,
+                "after": new,
+            })),
+            None => removed.push(old),
+        }
+    }
+    let added: Vec<Value> = after.into_values().collect();
+
+    json!({
+        "added": added,
+        "removed": removed,
+        "changed": changed,
+        "unchanged_count": unchanged,
+    })
+}
+
+/// Top-level fields whose values differ between two documents
+fn changed_fields(old: &Value, new: &Value) -> Vec<String> {
+    let empty = Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+
+    let mut fields: Vec<String> = old.keys()
+        .chain(new.keys().filter(|k| !old.contains_key(*k)))
+        .filter(|k| old.get(*k) != new.get(*k))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields
+}
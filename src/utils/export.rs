@@ -1,7 +1,46 @@
 use serde_json::Value;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// How a CSV column is rendered for typed consumers (BigQuery, Postgres COPY, ...)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    String,
+    Number,
+    Boolean,
+    Date,
+    Json,
+}
+
+impl ColumnType {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "string" => Ok(ColumnType::String),
+            "number" => Ok(ColumnType::Number),
+            "boolean" => Ok(ColumnType::Boolean),
+            "date" => Ok(ColumnType::Date),
+            "json" => Ok(ColumnType::Json),
+            other => Err(format!(
+                "Invalid column type '{}'. Use 'string', 'number', 'boolean', 'date' or 'json'",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    pub column_types: HashMap<String, ColumnType>,
+    /// Written for null and missing values, e.g. `\N` for Postgres COPY
+    pub null_token: String,
+}
 
 pub fn to_csv(documents: &[Value], headers: Option<Vec<String>>) -> Result<String, String> {
+    to_csv_with_options(documents, headers, &CsvOptions::default())
+}
+
+pub fn to_csv_with_options(documents: &[Value], headers: Option<Vec<String>>, options: &CsvOptions) -> Result<String, String> {
     if documents.is_empty() {
         return Ok(String::new());
     }
@@ -23,9 +62,14 @@ pub fn to_csv(documents: &[Value], headers: Option<Vec<String>>) -> Result<Strin
     for doc in documents {
         let mut row = Vec::new();
         for header in &header_list {
-            let value = doc.get(header)
-                .map(|v| format_value_for_csv(v))
-                .unwrap_or_else(|| String::new());
+            let value = match doc.get(header) {
+                None | Some(Value::Null) => options.null_token.clone(),
+                Some(v) => match options.column_types.get(header) {
+                    Some(column_type) => format_typed_value(v, *column_type)
+                        .map_err(|e| format!("Column '{}': {}", header, e))?,
+                    None => format_value_for_csv(v),
+                },
+            };
             row.push(escape_csv_field(&value));
         }
         csv.push_str(&row.join(","));
@@ -35,6 +79,83 @@ pub fn to_csv(documents: &[Value], headers: Option<Vec<String>>) -> Result<Strin
     Ok(csv)
 }
 
+/// Unwrap Extended JSON numbers (`{"$numberLong": "5"}`, ...) to their literal text
+fn extended_number(value: &Value) -> Option<String> {
+    let map = value.as_object()?;
+    ["$numberLong", "$numberInt", "$numberDouble", "$numberDecimal"]
+        .iter()
+        .find_map(|key| map.get(*key))
+        .and_then(|n| n.as_str().map(|s| s.to_string()))
+}
+
+/// ISO-8601 text for `{"$date": ...}` (canonical or relaxed), epoch milliseconds, or a date string
+fn format_date(value: &Value) -> Option<String> {
+    let inner = value.get("$date").unwrap_or(value);
+    let millis = match inner {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => {
+            return chrono::DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|d| d.with_timezone(&chrono::Utc).to_rfc3339_opts(chrono::SecondsFormat::Millis, true));
+        }
+        other => extended_number(other).and_then(|n| n.parse::<i64>().ok()),
+    }?;
+    chrono::DateTime::<chrono::Utc>::from_timestamp_millis(millis)
+        .map(|d| d.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+}
+
+/// Render an Extended JSON wrapper as a plain cell: ObjectIds as hex, dates as
+/// ISO-8601 and numbers as their literal text
+fn extended_scalar(value: &Value) -> Option<String> {
+    let map = value.as_object()?;
+    if map.len() != 1 {
+        return None;
+    }
+    if let Some(oid) = map.get("$oid") {
+        return oid.as_str().map(|hex| hex.to_string());
+    }
+    if map.contains_key("$date") {
+        return format_date(value);
+    }
+    extended_number(value)
+}
+
+fn format_typed_value(value: &Value, column_type: ColumnType) -> Result<String, String> {
+    match column_type {
+        ColumnType::String => Ok(match value {
+            Value::String(s) => s.clone(),
+            other => format_value_for_csv(other),
+        }),
+        ColumnType::Number => match value {
+            Value::Number(n) => Ok(n.to_string()),
+            other => extended_number(other).ok_or_else(|| format!("{} is not a number", other)),
+        },
+        ColumnType::Boolean => match value {
+            Value::Bool(b) => Ok(b.to_string()),
+            Value::Number(n) => Ok((n.as_f64() != Some(0.0)).to_string()),
+            Value::String(s) if s.eq_ignore_ascii_case("true") || s.eq_ignore_ascii_case("false") => Ok(s.to_lowercase()),
+            other => Err(format!("{} is not a boolean", other)),
+        },
+        ColumnType::Date => format_date(value).ok_or_else(|| format!("{} is not a date", value)),
+        ColumnType::Json => serde_json::to_string(value).map_err(|e| e.to_string()),
+    }
+}
+
+/// Extract the given dotted paths (array indices allowed, e.g. `items.0.sku`) into
+/// flat rows keyed by path. Missing paths become null.
+pub fn flatten_paths(documents: &[Value], paths: &[String]) -> Vec<Value> {
+    documents
+        .iter()
+        .map(|doc| {
+            let row: serde_json::Map<String, Value> = paths
+                .iter()
+                .map(|path| (path.clone(), crate::utils::matcher::get_path(doc, path).cloned().unwrap_or(Value::Null)))
+                .collect();
+            Value::Object(row)
+        })
+        .collect()
+}
+
 fn extract_keys(value: &Value) -> Vec<String> {
     match value {
         Value::Object(map) => {
@@ -56,7 +177,8 @@ fn extract_keys_recursive(map: &serde_json::Map<String, Value>, keys: &mut Vec<S
         };
 
         match value {
-            Value::Object(nested_map) => {
+            // Extended JSON wrappers like `{"$oid": ...}` are single cells, not nested columns
+            Value::Object(nested_map) if extended_scalar(value).is_none() => {
                 extract_keys_recursive(nested_map, keys, full_key);
             }
             _ => {
@@ -78,7 +200,8 @@ fn format_value_for_csv(value: &Value) -> String {
             let items: Vec<String> = arr.iter().map(|v| format_value_for_csv(v)).collect();
             format!("[{}]", items.join(";"))
         }
-        Value::Object(_) => serde_json::to_string(value).unwrap_or_else(|_| String::new()),
+        Value::Object(_) => extended_scalar(value)
+            .unwrap_or_else(|| serde_json::to_string(value).unwrap_or_else(|_| String::new())),
     }
 }
 
@@ -100,3 +223,129 @@ pub fn to_json(documents: &[Value], pretty: bool) -> Result<String, String> {
     }
 }
 
+
+pub fn to_ndjson(documents: &[Value]) -> Result<String, String> {
+    let mut ndjson = String::new();
+    for doc in documents {
+        let line = serde_json::to_string(doc)
+            .map_err(|e| format!("Failed to serialize to NDJSON: {}", e))?;
+        ndjson.push_str(&line);
+        ndjson.push('\n');
+    }
+    Ok(ndjson)
+}
+
+/// An NDJSON file opened once in append mode and written one line per document,
+/// for logs that grow for as long as a listener runs
+pub struct NdjsonAppender {
+    file: tokio::fs::File,
+    path: PathBuf,
+}
+
+impl NdjsonAppender {
+    pub async fn open(path: &Path) -> Result<Self, String> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Ok(NdjsonAppender { file, path: path.to_path_buf() })
+    }
+
+    pub async fn append(&mut self, document: &Value) -> Result<(), String> {
+        let mut line = serde_json::to_string(document)
+            .map_err(|e| format!("Failed to serialize to NDJSON: {}", e))?;
+        line.push('\n');
+
+        self.file.write_all(line.as_bytes()).await
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        self.file.flush().await
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn typed(columns: &[(&str, ColumnType)], null_token: &str) -> CsvOptions {
+        CsvOptions {
+            column_types: columns.iter().map(|(name, t)| (name.to_string(), *t)).collect(),
+            null_token: null_token.to_string(),
+        }
+    }
+
+    #[test]
+    fn date_columns_format_as_iso_8601() {
+        let documents = vec![
+            json!({ "name": "a", "created": { "$date": { "$numberLong": "1714557600000" } } }),
+            json!({ "name": "b", "created": { "$date": "2024-05-01T12:00:00+02:00" } }),
+            json!({ "name": "c", "created": 1714557600000i64 }),
+        ];
+        let options = typed(&[("created", ColumnType::Date)], "");
+        let csv = to_csv_with_options(&documents, Some(vec!["name".into(), "created".into()]), &options).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "name,created",
+                "a,2024-05-01T10:00:00.000Z",
+                "b,2024-05-01T10:00:00.000Z",
+                "c,2024-05-01T10:00:00.000Z",
+            ]
+        );
+
+        let bad = vec![json!({ "created": "yesterday" })];
+        let err = to_csv_with_options(&bad, Some(vec!["created".into()]), &options).unwrap_err();
+        assert!(err.starts_with("Column 'created':"), "{}", err);
+    }
+
+    #[test]
+    fn custom_null_token_marks_null_and_missing_values() {
+        let documents = vec![
+            json!({ "name": "a", "active": true, "note": null }),
+            json!({ "name": "b", "active": "FALSE" }),
+        ];
+        let options = typed(&[("active", ColumnType::Boolean)], "\\N");
+        let csv = to_csv_with_options(&documents, Some(vec!["name".into(), "active".into(), "note".into()]), &options).unwrap();
+        assert_eq!(csv.lines().collect::<Vec<_>>(), vec!["name,active,note", "a,true,\\N", "b,false,\\N"]);
+
+        // The default keeps nulls empty
+        let csv = to_csv(&documents, Some(vec!["name".into(), "note".into()])).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("a,"));
+    }
+
+    #[test]
+    fn flatten_paths_extracts_nested_and_indexed_values() {
+        let documents = vec![
+            json!({ "_id": 1, "customer": { "name": "Ada" }, "items": [{ "sku": "A1" }, { "sku": "B2" }] }),
+            json!({ "_id": 2, "customer": { "name": "Bo" }, "items": [] }),
+        ];
+        let paths = vec!["customer.name".to_string(), "items.1.sku".to_string()];
+        let rows = flatten_paths(&documents, &paths);
+        assert_eq!(rows[0], json!({ "customer.name": "Ada", "items.1.sku": "B2" }));
+        assert_eq!(rows[1], json!({ "customer.name": "Bo", "items.1.sku": null }));
+
+        let csv = to_csv_with_options(&rows, Some(paths), &CsvOptions::default()).unwrap();
+        assert_eq!(csv.lines().collect::<Vec<_>>(), vec!["customer.name,items.1.sku", "Ada,B2", "Bo,"]);
+    }
+
+    #[test]
+    fn object_ids_and_dates_become_plain_cells() {
+        let documents = vec![json!({
+            "_id": { "$oid": "65f1c0ffee0000000000abcd" },
+            "created": { "$date": "2024-05-01T10:00:00Z" },
+            "qty": { "$numberLong": "12" },
+            "tags": [{ "$oid": "65f1c0ffee0000000000abce" }],
+        })];
+        let csv = to_csv(&documents, None).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "_id,created,qty,tags",
+                "65f1c0ffee0000000000abcd,2024-05-01T10:00:00.000Z,12,[65f1c0ffee0000000000abce]",
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use serde_json::Value;
+use std::io::{self, BufRead, Bytes, Read};
+use std::path::Path;
+
+/// Layout of an import file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    /// One JSON document per line
+    Ndjson,
+    /// A single top-level JSON array of documents
+    JsonArray,
+}
+
+impl ImportFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "ndjson" | "jsonl" => Ok(ImportFormat::Ndjson),
+            "json" => Ok(ImportFormat::JsonArray),
+            other => Err(format!("Invalid import format '{}'. Use 'ndjson' or 'json'", other)),
+        }
+    }
+
+    /// `.json` files are read as an array, anything else as NDJSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ImportFormat::JsonArray,
+            _ => ImportFormat::Ndjson,
+        }
+    }
+}
+
+/// A parsed record and the line it starts on; parse failures are kept per record
+/// so one malformed document does not abort the import
+pub type ImportRecord = (usize, Result<Value, String>);
+
+/// Stream records from `reader` one at a time, never holding more than a single
+/// document in memory
+pub fn records<R: BufRead + Send + 'static>(
+    reader: R,
+    format: ImportFormat,
+) -> Box<dyn Iterator<Item = io::Result<ImportRecord>> + Send> {
+    match format {
+        ImportFormat::Ndjson => Box::new(NdjsonRecords { lines: reader.lines(), line: 0 }),
+        ImportFormat::JsonArray => Box::new(JsonArrayRecords {
+            bytes: reader.bytes(),
+            line: 1,
+            started: false,
+            done: false,
+        }),
+    }
+}
+
+struct NdjsonRecords<R> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> Iterator for NdjsonRecords<R> {
+    type Item = io::Result<ImportRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(e) => return Some(Err(e)),
+            };
+            self.line += 1;
+            if text.trim().is_empty() {
+                continue;
+            }
+            return Some(Ok((self.line, serde_json::from_str(&text).map_err(|e| e.to_string()))));
+        }
+    }
+}
+
+/// Splits a top-level JSON array into elements by tracking nesting depth and
+/// string state, then parses each element on its own
+struct JsonArrayRecords<R> {
+    bytes: Bytes<R>,
+    line: usize,
+    started: bool,
+    done: bool,
+}
+
+impl<R: Read> JsonArrayRecords<R> {
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        match self.bytes.next().transpose()? {
+            Some(b'\n') => {
+                self.line += 1;
+                Ok(Some(b'\n'))
+            }
+            other => Ok(other),
+        }
+    }
+
+    fn read_element(&mut self, first: u8) -> io::Result<Result<Vec<u8>, String>> {
+        let mut buffer = vec![first];
+        let mut depth = usize::from(first == b'{' || first == b'[');
+        let mut in_string = first == b'"';
+        let mut escaped = false;
+
+        loop {
+            let Some(byte) = self.next_byte()? else {
+                self.done = true;
+                return Ok(Err("Unexpected end of file inside array element".to_string()));
+            };
+
+            if in_string {
+                buffer.push(byte);
+                if escaped {
+                    escaped = false;
+                } else if byte == b'\\' {
+                    escaped = true;
+                } else if byte == b'"' {
+                    in_string = false;
+                    if depth == 0 {
+                        return Ok(Ok(buffer));
+                    }
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth == 0 => {
+                    // The closing bracket of the array ends a trailing scalar
+                    self.done = true;
+                    return Ok(Ok(buffer));
+                }
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        buffer.push(byte);
+                        return Ok(Ok(buffer));
+                    }
+                }
+                b',' if depth == 0 => return Ok(Ok(buffer)),
+                _ => {}
+            }
+            buffer.push(byte);
+        }
+    }
+}
+
+impl<R: Read> Iterator for JsonArrayRecords<R> {
+    type Item = io::Result<ImportRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let byte = match self.next_byte() {
+                Ok(Some(byte)) => byte,
+                Ok(None) => {
+                    self.done = true;
+                    if !self.started {
+                        return None;
+                    }
+                    return Some(Ok((self.line, Err("Missing closing ']' at end of file".to_string()))));
+                }
+                Err(e) => return Some(Err(e)),
+            };
+            if byte.is_ascii_whitespace() || (self.started && byte == b',') {
+                continue;
+            }
+
+            if !self.started {
+                if byte != b'[' {
+                    self.done = true;
+                    return Some(Ok((self.line, Err("Expected a JSON array of documents".to_string()))));
+                }
+                self.started = true;
+                continue;
+            }
+            if byte == b']' {
+                self.done = true;
+                return None;
+            }
+
+            let line = self.line;
+            return Some(self.read_element(byte).map(|element| {
+                let value = element.and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()));
+                (line, value)
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(input: &str, format: ImportFormat) -> Vec<ImportRecord> {
+        records(io::Cursor::new(input.as_bytes().to_vec()), format)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn ndjson_reports_malformed_line_and_keeps_the_rest() {
+        let input = "{\"a\": 1}\n\n{\"a\": \n{\"a\": 3}\n";
+        let parsed = collect(input, ImportFormat::Ndjson);
+
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].0, 1);
+        assert_eq!(parsed[0].1.as_ref().unwrap()["a"], 1);
+        assert_eq!(parsed[1].0, 3);
+        assert!(parsed[1].1.is_err());
+        assert_eq!(parsed[2].0, 4);
+        assert_eq!(parsed[2].1.as_ref().unwrap()["a"], 3);
+    }
+
+    #[test]
+    fn json_array_splits_elements_with_nested_brackets_and_strings() {
+        let input = "[\n  {\"a\": [1, {\"b\": \"],{\"}]},\n  {\"c\": \"x\\\"y\"}\n]";
+        let parsed = collect(input, ImportFormat::JsonArray);
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].1.as_ref().unwrap()["a"][1]["b"], "],{");
+        assert_eq!(parsed[1].0, 3);
+        assert_eq!(parsed[1].1.as_ref().unwrap()["c"], "x\"y");
+    }
+
+    #[test]
+    fn json_array_requires_an_array() {
+        let parsed = collect("{\"a\": 1}", ImportFormat::JsonArray);
+        assert_eq!(parsed.len(), 1);
+        assert!(parsed[0].1.is_err());
+    }
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(ImportFormat::from_path(Path::new("dump.JSON")), ImportFormat::JsonArray);
+        assert_eq!(ImportFormat::from_path(Path::new("dump.ndjson")), ImportFormat::Ndjson);
+        assert!(ImportFormat::parse("csv").is_err());
+    }
+}
@@ -17,8 +17,325 @@ pub fn json_to_bson(value: Value) -> Result<Document, String> {
         .map_err(|e| format!("Failed to convert JSON to BSON value: {}", e))?;
     
     // Then convert BSON value to Document
-    match bson_value {
+    match translate_regexes(bson_value) {
         bson::Bson::Document(doc) => Ok(doc),
         _ => Err("JSON value must be an object to convert to Document".to_string()),
     }
 }
+
+/// Turn `{"$regex": ..., "$options": ...}` and `{"$regularExpression": {pattern, options}}`
+/// into real BSON regexes, so prefix matches like `^foo` can use an index
+fn translate_regexes(value: bson::Bson) -> bson::Bson {
+    match value {
+        bson::Bson::Document(doc) => match regex_from_document(&doc) {
+            Some(regex) => bson::Bson::RegularExpression(regex),
+            None => bson::Bson::Document(
+                doc.into_iter().map(|(key, value)| (key, translate_regexes(value))).collect()
+            ),
+        },
+        bson::Bson::Array(items) => bson::Bson::Array(items.into_iter().map(translate_regexes).collect()),
+        other => other,
+    }
+}
+
+fn regex_from_document(doc: &Document) -> Option<bson::Regex> {
+    let (pattern, options) = if let Ok(inner) = doc.get_document("$regularExpression") {
+        if doc.len() != 1 {
+            return None;
+        }
+        (inner.get_str("pattern").ok()?, inner.get_str("options").unwrap_or_default())
+    } else {
+        let pattern = doc.get_str("$regex").ok()?;
+        if doc.keys().any(|key| key != "$regex" && key != "$options") {
+            return None;
+        }
+        let options = match doc.get("$options") {
+            Some(options) => options.as_str()?,
+            None => "",
+        };
+        (pattern, options)
+    };
+
+    // The server expects regex flags in alphabetical order
+    let mut flags: Vec<char> = options.chars().collect();
+    flags.sort_unstable();
+    Some(bson::Regex {
+        pattern: pattern.to_string(),
+        options: flags.into_iter().collect(),
+    })
+}
+
+/// Normalize `_id` on an imported document: drop it so the server generates a fresh
+/// one, or turn a 24-hex-character string into a real ObjectId
+pub fn normalize_id(doc: &mut Document, coerce_object_ids: bool, drop_ids: bool) {
+    if drop_ids {
+        doc.remove("_id");
+        return;
+    }
+    if coerce_object_ids {
+        let oid = match doc.get("_id") {
+            Some(bson::Bson::String(id)) if id.len() == 24 => bson::oid::ObjectId::parse_str(id).ok(),
+            _ => None,
+        };
+        if let Some(oid) = oid {
+            doc.insert("_id", oid);
+        }
+    }
+}
+
+/// Convert an Extended JSON value (e.g. `{"$oid": "..."}`, `{"$date": ...}`) → BSON value
+pub fn extended_json_to_bson(value: Value) -> Result<bson::Bson, String> {
+    bson::Bson::try_from(value)
+        .map_err(|e| format!("Failed to parse Extended JSON value: {}", e))
+}
+
+/// Convert an Extended JSON object → Document, e.g. a record from an exported file
+pub fn extended_json_to_document(value: Value) -> Result<Document, String> {
+    match extended_json_to_bson(value)? {
+        bson::Bson::Document(doc) => Ok(doc),
+        _ => Err("Extended JSON value must be an object to convert to Document".to_string()),
+    }
+}
+
+/// Convert an RFC 6901 JSON Pointer (`/address/city`) → dotted field path (`address.city`)
+fn pointer_to_path(pointer: &str) -> Result<String, String> {
+    let segments = pointer
+        .strip_prefix('/')
+        .ok_or_else(|| format!("Invalid JSON Pointer '{}': must start with '/'", pointer))?
+        .split('/')
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect::<Vec<_>>();
+
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(format!("Invalid JSON Pointer '{}': empty path segment", pointer));
+    }
+    if segments.iter().any(|segment| segment == "-") {
+        return Err(format!("Appending with '-' is not supported in '{}'", pointer));
+    }
+    if segments[0] == "_id" {
+        return Err("Patching '_id' is not allowed".to_string());
+    }
+    Ok(segments.join("."))
+}
+
+/// Whether `a` and `b` are the same field or one contains the other
+fn paths_overlap(a: &str, b: &str) -> bool {
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    longer.strip_prefix(shorter).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Translate an RFC 6902 JSON Patch (add/remove/replace/move) → update document
+/// using `$set`, `$unset` and `$rename`. Values may use Extended JSON.
+pub fn json_patch_to_update(patch: &[Value]) -> Result<Document, String> {
+    let mut set = Document::new();
+    let mut unset = Document::new();
+    let mut rename = Document::new();
+
+    for (i, operation) in patch.iter().enumerate() {
+        let op = operation.get("op").and_then(Value::as_str)
+            .ok_or_else(|| format!("Patch operation {}: 'op' is required", i))?;
+        let path = operation.get("path").and_then(Value::as_str)
+            .ok_or_else(|| format!("Patch operation {}: 'path' is required", i))
+            .and_then(pointer_to_path)?;
+
+        match op {
+            "add" | "replace" => {
+                let value = operation.get("value").cloned()
+                    .ok_or_else(|| format!("Patch operation {}: 'value' is required for '{}'", i, op))?;
+                unset.remove(&path);
+                set.insert(path, extended_json_to_bson(value)?);
+            }
+            "remove" => {
+                set.remove(&path);
+                unset.insert(path, "");
+            }
+            "move" => {
+                let from = operation.get("from").and_then(Value::as_str)
+                    .ok_or_else(|| format!("Patch operation {}: 'from' is required for 'move'", i))
+                    .and_then(pointer_to_path)?;
+                rename.insert(from, path);
+            }
+            other => return Err(format!("Patch operation {}: unsupported op '{}'", i, other)),
+        }
+    }
+
+    // The server rejects an update touching a path and its parent (or the same path)
+    // under different operators, e.g. a move into a field the patch also sets
+    let paths: Vec<&str> = set.keys()
+        .chain(unset.keys())
+        .chain(rename.keys())
+        .map(String::as_str)
+        .chain(rename.values().filter_map(|to| to.as_str()))
+        .collect();
+    for (i, a) in paths.iter().enumerate() {
+        for b in &paths[i + 1..] {
+            if paths_overlap(a, b) {
+                return Err(format!("Patch operations on '{}' and '{}' conflict; apply them as separate patches", a, b));
+            }
+        }
+    }
+
+    let mut update = Document::new();
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    if !rename.is_empty() {
+        update.insert("$rename", rename);
+    }
+    if update.is_empty() {
+        return Err("Patch contains no operations".to_string());
+    }
+    Ok(update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_json_document_decodes_wrappers() {
+        let doc = extended_json_to_document(serde_json::json!({
+            "_id": { "$oid": "507f1f77bcf86cd799439011" },
+            "created": { "$date": "2024-01-02T03:04:05Z" },
+            "count": { "$numberLong": "42" },
+        })).unwrap();
+
+        assert!(matches!(doc.get("_id"), Some(bson::Bson::ObjectId(_))));
+        assert!(matches!(doc.get("created"), Some(bson::Bson::DateTime(_))));
+        assert_eq!(doc.get("count"), Some(&bson::Bson::Int64(42)));
+    }
+
+    #[test]
+    fn extended_json_document_rejects_non_objects() {
+        assert!(extended_json_to_document(serde_json::json!([1, 2])).is_err());
+    }
+
+    fn patch(operations: Value) -> Result<Document, String> {
+        json_patch_to_update(operations.as_array().unwrap())
+    }
+
+    #[test]
+    fn json_patch_maps_operations_to_update_operators() {
+        let update = patch(serde_json::json!([
+            { "op": "replace", "path": "/address/city", "value": "Paris" },
+            { "op": "add", "path": "/joined", "value": { "$date": "2024-01-02T03:04:05Z" } },
+            { "op": "remove", "path": "/legacy" },
+            { "op": "move", "from": "/nick", "path": "/nickname" },
+            { "op": "add", "path": "/a~1b", "value": 1 },
+        ])).unwrap();
+
+        let set = update.get_document("$set").unwrap();
+        assert_eq!(set.get_str("address.city"), Ok("Paris"));
+        assert!(matches!(set.get("joined"), Some(bson::Bson::DateTime(_))));
+        assert_eq!(set.get_i32("a/b"), Ok(1));
+        assert_eq!(update.get_document("$unset").unwrap(), &bson::doc! { "legacy": "" });
+        assert_eq!(update.get_document("$rename").unwrap(), &bson::doc! { "nick": "nickname" });
+    }
+
+    #[test]
+    fn json_patch_later_operations_win() {
+        let update = patch(serde_json::json!([
+            { "op": "add", "path": "/tmp", "value": 1 },
+            { "op": "remove", "path": "/tmp" },
+        ])).unwrap();
+        assert_eq!(update, bson::doc! { "$unset": { "tmp": "" } });
+    }
+
+    #[test]
+    fn json_patch_rejects_invalid_operations() {
+        assert!(patch(serde_json::json!([])).is_err());
+        assert!(patch(serde_json::json!([{ "op": "copy", "from": "/a", "path": "/b" }])).is_err());
+        assert!(patch(serde_json::json!([{ "op": "replace", "path": "/_id", "value": 1 }])).is_err());
+        assert!(patch(serde_json::json!([{ "op": "add", "path": "/tags/-", "value": 1 }])).is_err());
+        assert!(patch(serde_json::json!([{ "op": "add", "path": "name", "value": 1 }])).is_err());
+    }
+
+    #[test]
+    fn json_patch_rejects_move_and_set_on_overlapping_paths() {
+        let error = patch(serde_json::json!([
+            { "op": "move", "from": "/old", "path": "/profile" },
+            { "op": "replace", "path": "/profile/name", "value": "x" },
+        ])).unwrap_err();
+        assert!(error.contains("profile.name") && error.contains("profile"), "{}", error);
+
+        assert!(patch(serde_json::json!([
+            { "op": "move", "from": "/a", "path": "/b" },
+            { "op": "replace", "path": "/a", "value": 1 },
+        ])).is_err());
+        assert!(patch(serde_json::json!([
+            { "op": "move", "from": "/a", "path": "/b" },
+            { "op": "replace", "path": "/ab", "value": 1 },
+        ])).is_ok());
+    }
+
+    #[test]
+    fn normalize_id_coerces_hex_strings() {
+        let mut doc = bson::doc! { "_id": "507f1f77bcf86cd799439011", "name": "a" };
+        normalize_id(&mut doc, true, false);
+        assert_eq!(doc.get_object_id("_id").unwrap().to_hex(), "507f1f77bcf86cd799439011");
+
+        // Other strings are left alone, as is everything without the option
+        let mut doc = bson::doc! { "_id": "order-42" };
+        normalize_id(&mut doc, true, false);
+        assert_eq!(doc.get_str("_id"), Ok("order-42"));
+        let mut doc = bson::doc! { "_id": "zz7f1f77bcf86cd799439011" };
+        normalize_id(&mut doc, true, false);
+        assert_eq!(doc.get_str("_id"), Ok("zz7f1f77bcf86cd799439011"));
+        let mut doc = bson::doc! { "_id": "507f1f77bcf86cd799439011" };
+        normalize_id(&mut doc, false, false);
+        assert_eq!(doc.get_str("_id"), Ok("507f1f77bcf86cd799439011"));
+    }
+
+    #[test]
+    fn normalize_id_drops_ids() {
+        let mut doc = bson::doc! { "_id": "507f1f77bcf86cd799439011", "name": "a" };
+        normalize_id(&mut doc, true, true);
+        assert_eq!(doc, bson::doc! { "name": "a" });
+    }
+
+    #[test]
+    fn regex_objects_become_bson_regexes() {
+        let doc = json_to_bson(serde_json::json!({
+            "name": { "$regex": "^foo\\.", "$options": "xi" },
+            "$or": [{ "sku": { "$regularExpression": { "pattern": "^A", "options": "" } } }],
+        })).unwrap();
+
+        assert_eq!(doc.get("name"), Some(&bson::Bson::RegularExpression(bson::Regex {
+            pattern: "^foo\\.".to_string(),
+            options: "ix".to_string(),
+        })));
+        let sku = doc.get_array("$or").unwrap()[0].as_document().unwrap().get("sku").unwrap();
+        assert!(matches!(sku, bson::Bson::RegularExpression(regex) if regex.pattern == "^A"));
+    }
+
+    #[test]
+    fn regex_with_other_operators_stays_a_document() {
+        let doc = json_to_bson(serde_json::json!({ "name": { "$regex": "^foo", "$ne": "food" } })).unwrap();
+        assert!(doc.get_document("name").is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn prefix_regexes_match_and_use_an_index() {
+        use futures::TryStreamExt;
+
+        let db = crate::mongo::test_support::scratch_database().await;
+        let coll = db.collection::<Document>("products");
+        coll.insert_many(["Foobar", "foot", "bar foo"].map(|name| bson::doc! { "name": name }), None).await.unwrap();
+        coll.create_index(mongodb::IndexModel::builder().keys(bson::doc! { "name": 1 }).build(), None).await.unwrap();
+
+        let insensitive = json_to_bson(serde_json::json!({ "name": { "$regex": "^foo", "$options": "i" } })).unwrap();
+        let matched: Vec<Document> = coll.find(insensitive, None).await.unwrap().try_collect().await.unwrap();
+        assert_eq!(matched.len(), 2);
+
+        let anchored = json_to_bson(serde_json::json!({ "name": { "$regex": "^foo" } })).unwrap();
+        let plan = crate::mongo::performance::explain_find_plan(coll, anchored, None).await.unwrap();
+        let summary = crate::mongo::performance::summarize_plan(&plan);
+        assert!(summary.stages.iter().any(|stage| stage == "IXSCAN"), "{:?}", summary);
+
+        db.drop(None).await.unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+use serde_json::Value;
+
+/// Resolve a dotted path (e.g. "ns.coll" or "items.0.sku") inside a JSON value
+pub fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    for part in path.split('.') {
+        current = match current {
+            Value::Object(map) => map.get(part)?,
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Check that every `path: expected` pair in `filter` equals the value at that path
+pub fn matches_filter(value: &Value, filter: &Value) -> bool {
+    match filter {
+        Value::Object(conditions) => conditions
+            .iter()
+            .all(|(path, expected)| get_path(value, path) == Some(expected)),
+        Value::Null => true,
+        _ => false,
+    }
+}
+
+/// Case-insensitive substring search over the serialized value
+pub fn matches_text(value: &Value, text: &str) -> bool {
+    let needle = text.to_lowercase();
+    value.to_string().to_lowercase().contains(&needle)
+}
+
+/// Comparable form of a value for range operators: numbers (including Extended JSON
+/// number wrappers and `$date`) as `f64`, strings and `$oid` as text
+#[derive(PartialEq)]
+enum Comparable<'a> {
+    Number(f64),
+    Text(&'a str),
+}
+
+/// Like the server's range operators, a number and a string never compare
+impl PartialOrd for Comparable<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Comparable::Number(a), Comparable::Number(b)) => a.partial_cmp(b),
+            (Comparable::Text(a), Comparable::Text(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+fn comparable(value: &Value) -> Option<Comparable<'_>> {
+    match value {
+        Value::Number(n) => n.as_f64().map(Comparable::Number),
+        Value::String(s) => Some(Comparable::Text(s)),
+        Value::Object(map) if map.len() == 1 => {
+            let (key, inner) = map.iter().next()?;
+            match key.as_str() {
+                "$oid" => inner.as_str().map(Comparable::Text),
+                "$numberLong" | "$numberInt" | "$numberDouble" | "$numberDecimal" => {
+                    inner.as_str()?.parse::<f64>().ok().map(Comparable::Number)
+                }
+                "$date" => match inner {
+                    Value::String(s) => chrono::DateTime::parse_from_rfc3339(s)
+                        .ok()
+                        .map(|d| Comparable::Number(d.timestamp_millis() as f64)),
+                    other => comparable(other),
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn values_equal(actual: &Value, expected: &Value) -> bool {
+    actual == expected || matches!((comparable(actual), comparable(expected)), (Some(a), Some(b)) if a == b)
+}
+
+/// Match `^prefix`, `suffix$`, `^exact$` and plain substring patterns; anything using
+/// other regex syntax is reported as unsupported
+fn matches_simple_regex(text: &str, pattern: &str, options: &str) -> Result<bool, String> {
+    let anchored_start = pattern.starts_with('^');
+    let anchored_end = pattern.ends_with('$') && !pattern.ends_with("\\$");
+    let literal = &pattern[usize::from(anchored_start)..pattern.len() - usize::from(anchored_end)];
+    if literal.chars().any(|c| ".*+?()[]{}|\\^$".contains(c)) {
+        return Err(format!("Regex '{}' is too complex to evaluate locally", pattern));
+    }
+
+    let (text, literal) = if options.contains('i') {
+        (text.to_lowercase(), literal.to_lowercase())
+    } else {
+        (text.to_string(), literal.to_string())
+    };
+    Ok(match (anchored_start, anchored_end) {
+        (true, true) => text == literal,
+        (true, false) => text.starts_with(&literal),
+        (false, true) => text.ends_with(&literal),
+        (false, false) => text.contains(&literal),
+    })
+}
+
+/// Evaluate one operator against a field value; arrays match when any element does
+fn matches_operator(actual: Option<&Value>, operator: &str, operand: &Value, options: &str) -> Result<bool, String> {
+    if operator == "$exists" {
+        let wanted = operand.as_bool().unwrap_or_else(|| operand.as_i64().is_some_and(|n| n != 0));
+        return Ok(actual.is_some() == wanted);
+    }
+    let Some(actual) = actual else {
+        return Ok(false);
+    };
+    if let Value::Array(items) = actual {
+        // Whole-array equality, or any element matching
+        if operator == "$eq" && values_equal(actual, operand) {
+            return Ok(true);
+        }
+        for item in items {
+            if matches_operator(Some(item), operator, operand, options)? {
+                return Ok(true);
+            }
+        }
+        return Ok(false);
+    }
+
+    let ordering = || comparable(actual).zip(comparable(operand))
+        .and_then(|(a, b)| a.partial_cmp(&b));
+    Ok(match operator {
+        "$eq" => values_equal(actual, operand),
+        "$gt" => ordering() == Some(std::cmp::Ordering::Greater),
+        "$gte" => matches!(ordering(), Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal)),
+        "$lt" => ordering() == Some(std::cmp::Ordering::Less),
+        "$lte" => matches!(ordering(), Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal)),
+        "$in" => operand
+            .as_array()
+            .ok_or("$in requires an array")?
+            .iter()
+            .any(|candidate| values_equal(actual, candidate)),
+        "$regex" => match (actual.as_str(), operand.as_str()) {
+            (Some(text), Some(pattern)) => matches_simple_regex(text, pattern, options)?,
+            (_, None) => return Err("$regex requires a string pattern".to_string()),
+            _ => false,
+        },
+        other => return Err(format!("Operator '{}' cannot be evaluated locally", other)),
+    })
+}
+
+const SUPPORTED_OPERATORS: [&str; 9] = ["$eq", "$gt", "$gte", "$lt", "$lte", "$in", "$exists", "$regex", "$options"];
+
+/// Check up front that `matches_query` can evaluate every operator in `filter`,
+/// including ones nested under fields, which would otherwise only fail once a
+/// document carrying that field is seen
+pub fn validate_query(filter: &Value) -> Result<(), String> {
+    let conditions = match filter {
+        Value::Object(conditions) => conditions,
+        Value::Null => return Ok(()),
+        _ => return Err("Filter must be an object".to_string()),
+    };
+
+    for (key, condition) in conditions {
+        if key == "$and" {
+            for clause in condition.as_array().ok_or("$and requires an array")? {
+                validate_query(clause)?;
+            }
+            continue;
+        }
+        if key.starts_with('$') {
+            return Err(format!("Operator '{}' cannot be evaluated locally", key));
+        }
+
+        let Value::Object(operators) = condition else {
+            continue;
+        };
+        if !operators.keys().any(|k| k.starts_with('$')) {
+            continue;
+        }
+        for (operator, operand) in operators {
+            if !SUPPORTED_OPERATORS.contains(&operator.as_str()) {
+                return Err(format!("Operator '{}' cannot be evaluated locally", operator));
+            }
+            match operator.as_str() {
+                "$in" if !operand.is_array() => return Err("$in requires an array".to_string()),
+                "$options" if !operand.is_string() => return Err("$options must be a string".to_string()),
+                "$regex" => {
+                    let pattern = operand.as_str().ok_or("$regex requires a string pattern")?;
+                    matches_simple_regex("", pattern, "")?;
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Evaluate a query filter locally, supporting implicit equality, `$eq`, `$gt`, `$gte`,
+/// `$lt`, `$lte`, `$in`, `$exists`, `$regex` (simple patterns) and `$and`
+pub fn matches_query(value: &Value, filter: &Value) -> Result<bool, String> {
+    let conditions = match filter {
+        Value::Object(conditions) => conditions,
+        Value::Null => return Ok(true),
+        _ => return Err("Filter must be an object".to_string()),
+    };
+
+    for (key, condition) in conditions {
+        let matched = if key == "$and" {
+            let clauses = condition.as_array().ok_or("$and requires an array")?;
+            let mut all = true;
+            for clause in clauses {
+                all &= matches_query(value, clause)?;
+            }
+            all
+        } else if key.starts_with('$') {
+            return Err(format!("Operator '{}' cannot be evaluated locally", key));
+        } else {
+            let actual = get_path(value, key);
+            match condition {
+                Value::Object(operators) if operators.keys().any(|k| k.starts_with('$')) => {
+                    let options = operators.get("$options").and_then(Value::as_str).unwrap_or_default();
+                    let mut all = true;
+                    for (operator, operand) in operators.iter().filter(|(k, _)| *k != "$options") {
+                        all &= matches_operator(actual, operator, operand, options)?;
+                    }
+                    all
+                }
+                expected => matches_operator(actual, "$eq", expected, "")?,
+            }
+        };
+        if !matched {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn validate_query_rejects_nested_unsupported_operators() {
+        let error = validate_query(&json!({ "a": { "$ne": 1 } })).unwrap_err();
+        assert!(error.contains("$ne"), "{}", error);
+        assert!(validate_query(&json!({ "$and": [{ "a": { "$elemMatch": {} } }] })).is_err());
+        assert!(validate_query(&json!({ "$or": [{ "a": 1 }] })).is_err());
+        assert!(validate_query(&json!({ "a": { "$regex": "a.*b" } })).is_err());
+    }
+
+    #[test]
+    fn validate_query_accepts_supported_filters() {
+        assert!(validate_query(&json!({
+            "status": "active",
+            "age": { "$gte": 18, "$lt": 65 },
+            "name": { "$regex": "^jo", "$options": "i" },
+            "$and": [{ "tags": { "$in": ["a", "b"] } }, { "deleted": { "$exists": false } }],
+            "address": { "city": "Paris" },
+        })).is_ok());
+    }
+
+    #[test]
+    fn matches_query_equality() {
+        let doc = json!({ "status": "active", "count": { "$numberLong": "5" }, "tags": ["a", "b"], "address": { "city": "Paris" } });
+        assert!(matches_query(&doc, &json!({ "status": "active" })).unwrap());
+        assert!(matches_query(&doc, &json!({ "count": 5 })).unwrap());
+        assert!(matches_query(&doc, &json!({ "tags": "b" })).unwrap());
+        assert!(matches_query(&doc, &json!({ "tags": ["a", "b"] })).unwrap());
+        assert!(matches_query(&doc, &json!({ "address.city": { "$eq": "Paris" } })).unwrap());
+        assert!(!matches_query(&doc, &json!({ "status": "inactive" })).unwrap());
+        assert!(!matches_query(&doc, &json!({ "count": "5" })).unwrap());
+    }
+
+    #[test]
+    fn matches_query_ranges() {
+        let doc = json!({ "age": 30, "name": "mia", "at": { "$date": "2024-01-02T00:00:00Z" } });
+        assert!(matches_query(&doc, &json!({ "age": { "$gt": 18, "$lte": 30 } })).unwrap());
+        assert!(!matches_query(&doc, &json!({ "age": { "$lt": 30 } })).unwrap());
+        assert!(matches_query(&doc, &json!({ "name": { "$gte": "m" } })).unwrap());
+        assert!(matches_query(&doc, &json!({ "at": { "$gt": { "$date": "2024-01-01T00:00:00Z" } } })).unwrap());
+        assert!(!matches_query(&doc, &json!({ "missing": { "$gt": 0 } })).unwrap());
+    }
+
+    #[test]
+    fn range_operators_do_not_compare_numbers_with_strings() {
+        let number = json!({ "v": 5 });
+        let text = json!({ "v": "5" });
+        for operator in ["$gt", "$gte", "$lt", "$lte"] {
+            assert!(!matches_query(&number, &json!({ "v": { operator: "a" } })).unwrap(), "{}", operator);
+            assert!(!matches_query(&text, &json!({ "v": { operator: 100 } })).unwrap(), "{}", operator);
+        }
+    }
+
+    fn events() -> Vec<Value> {
+        vec![
+            json!({ "operationType": "insert", "ns": { "db": "shop", "coll": "orders" }, "fullDocument": { "sku": "A-1" } }),
+            json!({ "operationType": "delete", "ns": { "db": "shop", "coll": "orders" } }),
+            json!({ "operationType": "delete", "ns": { "db": "shop", "coll": "users" } }),
+        ]
+    }
+
+    #[test]
+    fn matches_filter_by_operation_type() {
+        let deletes = events().iter().filter(|e| matches_filter(e, &json!({ "operationType": "delete" }))).count();
+        assert_eq!(deletes, 2);
+    }
+
+    #[test]
+    fn matches_filter_by_dotted_path() {
+        let filter = json!({ "ns.coll": "orders", "operationType": "delete" });
+        let matched: Vec<bool> = events().iter().map(|e| matches_filter(e, &filter)).collect();
+        assert_eq!(matched, vec![false, true, false]);
+        assert!(matches_filter(&events()[0], &Value::Null));
+        assert!(!matches_filter(&events()[0], &json!({ "ns.coll.name": "orders" })));
+    }
+
+    #[test]
+    fn matches_text_ignores_case() {
+        assert!(matches_text(&events()[0], "a-1"));
+        assert!(!matches_text(&events()[1], "a-1"));
+    }
+}
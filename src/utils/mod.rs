@@ -0,0 +1,6 @@
+pub mod json;
+pub mod export;
+pub mod import;
+pub mod matcher;
+pub mod size;
+pub mod diff;
@@ -0,0 +1,153 @@
+use mongodb::bson::{self, Bson, Document};
+use serde_json::Value;
+
+/// Maximum size of a single BSON document accepted by the server
+pub const MAX_BSON_DOCUMENT_SIZE: usize = 16 * 1024 * 1024;
+
+/// Estimate the encoded BSON size of a JSON value, as `$bsonSize` would report it
+/// for the document produced by `json::json_to_bson`
+pub fn bson_size(value: &Value) -> usize {
+    match bson::to_bson(value) {
+        Ok(Bson::Document(doc)) => document_size(&doc),
+        Ok(other) => value_size(&other),
+        // Fall back to the JSON text length when the value has no BSON form
+        Err(_) => value.to_string().len(),
+    }
+}
+
+/// Encoded size of a document: int32 length prefix, elements, trailing NUL
+pub fn document_size(doc: &Document) -> usize {
+    4 + doc.iter().map(|(key, value)| element_size(key, value)).sum::<usize>() + 1
+}
+
+/// Reject a document the server would refuse for exceeding the BSON size limit
+pub fn check_document_size(doc: &Document) -> Result<(), String> {
+    let bytes = document_size(doc);
+    if bytes > MAX_BSON_DOCUMENT_SIZE {
+        return Err(format!("document exceeds 16MB BSON limit (estimated {} bytes)", bytes));
+    }
+    Ok(())
+}
+
+/// `check_document_size` for a batch, naming the index of the first oversized document
+pub fn check_documents_size(docs: &[Document]) -> Result<(), String> {
+    for (index, doc) in docs.iter().enumerate() {
+        check_document_size(doc).map_err(|e| format!("Document at index {}: {}", index, e))?;
+    }
+    Ok(())
+}
+
+/// Type tag, NUL-terminated key and the value itself
+fn element_size(key: &str, value: &Bson) -> usize {
+    1 + key.len() + 1 + value_size(value)
+}
+
+fn string_size(s: &str) -> usize {
+    4 + s.len() + 1
+}
+
+fn value_size(value: &Bson) -> usize {
+    match value {
+        Bson::Double(_) => 8,
+        Bson::String(s) | Bson::Symbol(s) | Bson::JavaScriptCode(s) => string_size(s),
+        Bson::Document(doc) => document_size(doc),
+        // Arrays are encoded as documents keyed "0", "1", ...
+        Bson::Array(items) => {
+            4 + items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| element_size(&i.to_string(), item))
+                .sum::<usize>()
+                + 1
+        }
+        Bson::Binary(binary) => 4 + 1 + binary.bytes.len(),
+        Bson::ObjectId(_) => 12,
+        Bson::Boolean(_) => 1,
+        Bson::DateTime(_) => 8,
+        Bson::Null | Bson::Undefined | Bson::MinKey | Bson::MaxKey => 0,
+        Bson::RegularExpression(regex) => regex.pattern.len() + 1 + regex.options.len() + 1,
+        Bson::JavaScriptCodeWithScope(code) => 4 + string_size(&code.code) + document_size(&code.scope),
+        Bson::Int32(_) => 4,
+        Bson::Timestamp(_) => 8,
+        Bson::Int64(_) => 8,
+        Bson::Decimal128(_) => 16,
+        // Fields are private, so encode it under an empty key and subtract the
+        // wrapper: length prefix, type tag, key terminator and trailing NUL
+        Bson::DbPointer(_) => bson::to_vec(&bson::doc! { "": value.clone() })
+            .map(|bytes| bytes.len().saturating_sub(4 + 1 + 1 + 1))
+            .unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mongo::test_support::scratch_database;
+    use futures::StreamExt;
+
+    fn samples() -> Vec<Document> {
+        vec![
+            Document::new(),
+            bson::doc! { "_id": bson::oid::ObjectId::new(), "name": "Ada Lovelace", "age": 36, "score": 99.5 },
+            bson::doc! {
+                "tags": ["a", "bb", "ccc"],
+                "address": { "city": "Zürich", "zip": 8001 },
+                "created": bson::DateTime::now(),
+                "big": 1_i64 << 40,
+                "nothing": Bson::Null,
+                "flag": true,
+                "re": bson::Regex { pattern: "^a.*".to_string(), options: "i".to_string() },
+                "bin": bson::Binary { subtype: bson::spec::BinarySubtype::Generic, bytes: vec![1, 2, 3] },
+                "ts": bson::Timestamp { time: 1, increment: 2 },
+                "dec": "1.5".parse::<bson::Decimal128>().unwrap(),
+            },
+        ]
+    }
+
+    #[test]
+    fn document_size_matches_the_encoding() {
+        for doc in samples() {
+            assert_eq!(document_size(&doc), bson::to_vec(&doc).unwrap().len(), "{}", doc);
+        }
+    }
+
+    #[test]
+    fn bson_size_of_json_matches_the_encoding() {
+        let value = serde_json::json!({ "name": "Ada", "langs": ["en", "fr"], "age": 36, "ratio": 0.5, "nested": { "ok": true } });
+        let doc = bson::to_document(&value).unwrap();
+        assert_eq!(bson_size(&value), bson::to_vec(&doc).unwrap().len());
+    }
+
+    #[test]
+    fn oversized_documents_are_rejected() {
+        let doc = bson::doc! { "blob": "x".repeat(MAX_BSON_DOCUMENT_SIZE) };
+        assert!(check_document_size(&doc).unwrap_err().contains("16MB"));
+        assert!(check_document_size(&samples()[1]).is_ok());
+    }
+
+    #[test]
+    fn oversized_batch_documents_are_reported_by_index() {
+        let huge = bson::doc! { "_id": 2, "blob": "x".repeat(MAX_BSON_DOCUMENT_SIZE) };
+        let batch = vec![bson::doc! { "_id": 1 }, huge.clone(), bson::doc! { "_id": 3 }];
+        assert_eq!(
+            check_documents_size(&batch).unwrap_err(),
+            format!("Document at index 1: document exceeds 16MB BSON limit (estimated {} bytes)", document_size(&huge))
+        );
+        assert!(check_documents_size(&samples()).is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a MongoDB server at MONGODB_TEST_URI"]
+    async fn document_size_matches_the_server() {
+        let db = scratch_database().await;
+        for doc in samples() {
+            let pipeline = vec![
+                bson::doc! { "$documents": [{ "doc": doc.clone() }] },
+                bson::doc! { "$project": { "size": { "$bsonSize": "$doc" } } },
+            ];
+            let result = db.aggregate(pipeline, None).await.unwrap().next().await.unwrap().unwrap();
+            assert_eq!(result.get_i32("size").unwrap() as usize, document_size(&doc), "{}", doc);
+        }
+        db.drop(None).await.unwrap();
+    }
+}